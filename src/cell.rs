@@ -1,4 +1,7 @@
-use std::hash::{DefaultHasher, Hash, Hasher};
+use std::borrow::Borrow;
+use std::fmt;
+
+use crate::neighbourhood::Neighbourhood;
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum DeltaCoordinate {
@@ -8,10 +11,70 @@ pub enum DeltaCoordinate {
 }
 
 
-#[derive(Debug, Clone, PartialEq, Copy)]
-pub enum CellValue {
-    Set,
-    Unset
+// State of a cell, out of the k states of the rules. Unset is the quiescent
+// state every cell starts in, and Set the state rules set cells to. Binary
+// rules only have these two, rules of more states go through Unset, Set, 2 .. k - 1
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Copy, Hash)]
+pub struct CellValue(u8);
+
+// Beyond this, states don't fit in a CellValue
+pub const MAX_STATES: usize = 256;
+
+impl CellValue {
+    #[allow(non_upper_case_globals)]
+    pub const Unset: CellValue = CellValue(0);
+    #[allow(non_upper_case_globals)]
+    pub const Set: CellValue = CellValue(1);
+
+    pub fn new(state: usize) -> Self {
+        if state >= MAX_STATES {
+            panic!("Cells have at most {} states, not {}", MAX_STATES, state + 1);
+        }
+        CellValue(state as u8)
+    }
+
+    pub fn state(&self) -> usize {
+        self.0 as usize
+    }
+
+    // Whether the cell counts as set, which it does in every state but Unset
+    pub fn is_set(&self) -> bool {
+        *self != CellValue::Unset
+    }
+}
+
+impl From<bool> for CellValue {
+    fn from(set: bool) -> Self {
+        if set { CellValue::Set } else { CellValue::Unset }
+    }
+}
+
+impl fmt::Debug for CellValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CellValue::Unset => write!(f, "Unset"),
+            CellValue::Set => write!(f, "Set"),
+            CellValue(state) => write!(f, "State({})", state)
+        }
+    }
+}
+
+// Coordinates as a key of the cells of a space, of any number of dimensions. It
+// hashes and compares as the slice it borrows as, so a key is only allocated when
+// a cell is added, and cells are looked up by their coordinates as they are
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Coord(Box<[i32]>);
+
+impl Coord {
+    pub fn new(coordinates: &[i32]) -> Self {
+        Coord(coordinates.into())
+    }
+}
+
+impl Borrow<[i32]> for Coord {
+    fn borrow(&self) -> &[i32] {
+        &self.0
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -35,7 +98,7 @@ impl Cell {
     }
 
     pub fn get_value(&self) -> CellValue {
-        return self.value;
+        self.value
     }
 
     pub fn set_value(&mut self, v: CellValue) {
@@ -67,28 +130,20 @@ impl Cell {
         }
     }
 
-    pub fn get_hash(&self) -> u64 {
-        let mut hasher = DefaultHasher::new();
-        self.coordinates.hash(&mut hasher);
-        hasher.finish()
-    }
-
     pub fn get_nearby_coordinate(&self) -> Vec<i32> {
         
         let mut my_coordinates = self.coordinates.clone();
 
-        let mut i = 0;
-        for it in self.delta_coordinate.as_slice() {
+        for (i, it) in self.delta_coordinate.iter().enumerate() {
             if *it == DeltaCoordinate::Positive {
                 my_coordinates[i] += 1;
             }
             else if *it == DeltaCoordinate::Negative {
                 my_coordinates[i] -= 1;
             }
-            i += 1;
         }
 
-        return my_coordinates;
+        my_coordinates
     }
 
     pub fn has_unexplored_nearby_cell(&self) -> bool {
@@ -98,7 +153,7 @@ impl Cell {
             }
         }
 
-        return false;
+        false
     }
 
     // Goes from SameCoordinate -> Positive -> Negative
@@ -126,6 +181,13 @@ impl Cell {
         self.delta_coordinate = new_vec;
     }
 
+    // Coordinates of the cells a rule reads for this cell, the cell itself first
+    pub fn get_neighbourhood_coordinates(&self, neighbourhood: Neighbourhood) -> Vec<Vec<i32>> {
+        neighbourhood.inputs(self.len()).iter()
+            .map(|offset| self.coordinates.iter().zip(offset).map(|(a, b)| a + b).collect())
+            .collect()
+    }
+
     pub fn reset_explore(&mut self) {
         self.delta_coordinate = vec![DeltaCoordinate::SameCoordinate; self.delta_coordinate.len()];
     }   
@@ -148,6 +210,14 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_coord() {
+        assert_eq!(Coord::new(&[1, 2]), Coord::new(&[1, 2]));
+        assert_ne!(Coord::new(&[1, 2]), Coord::new(&[2, 1]));
+        assert_ne!(Coord::new(&[0, -1]), Coord::new(&[-1, 0]));
+        assert_ne!(Coord::new(&[1, 0]), Coord::new(&[1, 0, 0]));
+    }
+
     #[test]
     fn test_set_unset_flip() {
         let mut cell: Cell = Cell::new(10);
@@ -166,6 +236,12 @@ mod tests {
 
         cell.flip();
         assert_eq!(cell.get_value(), CellValue::Set);
+
+        cell.set_value(CellValue::new(2));
+        assert!(cell.get_value().is_set());
+        assert_eq!(cell.get_value().state(), 2);
+        assert_eq!(format!("{:?}", cell.get_value()), "State(2)");
+        assert_eq!(CellValue::from(false), CellValue::Unset);
     }
 
 
@@ -174,50 +250,67 @@ mod tests {
         let mut cell = Cell::new(1);
 
         assert_eq!(*cell.get_coordinates(), vec![0]);
-        assert_eq!(cell.has_unexplored_nearby_cell(), true);
+        assert!(cell.has_unexplored_nearby_cell());
         cell.generate_next_unexplored_nearby_cell();
         assert_eq!(*cell.get_coordinates(), vec![0]);
         assert_eq!(cell.get_nearby_coordinate(), vec![1]);
-        assert_eq!(cell.has_unexplored_nearby_cell(), true);
+        assert!(cell.has_unexplored_nearby_cell());
         cell.generate_next_unexplored_nearby_cell();
         assert_eq!(*cell.get_coordinates(), vec![0]);
         assert_eq!(cell.get_nearby_coordinate(), vec![-1]);
-        assert_eq!(cell.has_unexplored_nearby_cell(), false);
+        assert!(!cell.has_unexplored_nearby_cell());
 
         let mut two_d_cell = Cell::new(2);
         assert_eq!(*two_d_cell.get_coordinates(), vec![0, 0]);
-        assert_eq!(two_d_cell.has_unexplored_nearby_cell(), true);
+        assert!(two_d_cell.has_unexplored_nearby_cell());
         two_d_cell.generate_next_unexplored_nearby_cell();
         assert_eq!(*two_d_cell.get_coordinates(), vec![0, 0]);
         assert_eq!(two_d_cell.get_nearby_coordinate(), vec![1, 0]);
-        assert_eq!(two_d_cell.has_unexplored_nearby_cell(), true);
+        assert!(two_d_cell.has_unexplored_nearby_cell());
         two_d_cell.generate_next_unexplored_nearby_cell();
         assert_eq!(*two_d_cell.get_coordinates(), vec![0, 0]);
         assert_eq!(two_d_cell.get_nearby_coordinate(), vec![-1, 0]);
-        assert_eq!(two_d_cell.has_unexplored_nearby_cell(), true);
+        assert!(two_d_cell.has_unexplored_nearby_cell());
         two_d_cell.generate_next_unexplored_nearby_cell();
         assert_eq!(*two_d_cell.get_coordinates(), vec![0, 0]);
         assert_eq!(two_d_cell.get_nearby_coordinate(), vec![0, 1]);
-        assert_eq!(two_d_cell.has_unexplored_nearby_cell(), true);
+        assert!(two_d_cell.has_unexplored_nearby_cell());
         two_d_cell.generate_next_unexplored_nearby_cell();
         assert_eq!(*two_d_cell.get_coordinates(), vec![0, 0]);
         assert_eq!(two_d_cell.get_nearby_coordinate(), vec![1, 1]);
-        assert_eq!(two_d_cell.has_unexplored_nearby_cell(), true);
+        assert!(two_d_cell.has_unexplored_nearby_cell());
         two_d_cell.generate_next_unexplored_nearby_cell();
         assert_eq!(*two_d_cell.get_coordinates(), vec![0, 0]);
         assert_eq!(two_d_cell.get_nearby_coordinate(), vec![-1, 1]);
-        assert_eq!(two_d_cell.has_unexplored_nearby_cell(), true);
+        assert!(two_d_cell.has_unexplored_nearby_cell());
         two_d_cell.generate_next_unexplored_nearby_cell();
         assert_eq!(*two_d_cell.get_coordinates(), vec![0, 0]);
         assert_eq!(two_d_cell.get_nearby_coordinate(), vec![0, -1]);
-        assert_eq!(two_d_cell.has_unexplored_nearby_cell(), true);
+        assert!(two_d_cell.has_unexplored_nearby_cell());
         two_d_cell.generate_next_unexplored_nearby_cell();
         assert_eq!(*two_d_cell.get_coordinates(), vec![0, 0]);
         assert_eq!(two_d_cell.get_nearby_coordinate(), vec![1, -1]);
-        assert_eq!(two_d_cell.has_unexplored_nearby_cell(), true);
+        assert!(two_d_cell.has_unexplored_nearby_cell());
         two_d_cell.generate_next_unexplored_nearby_cell();
         assert_eq!(*two_d_cell.get_coordinates(), vec![0, 0]);
         assert_eq!(two_d_cell.get_nearby_coordinate(), vec![-1, -1]);
     }
 
+    #[test]
+    fn test_neighbourhood_coordinates() {
+        let mut cell = Cell::new(2);
+        cell.set_coordinates(vec![3, -2]);
+
+        assert_eq!(cell.get_neighbourhood_coordinates(Neighbourhood::VonNeumann(1)), vec![vec![3, -2], vec![4, -2], vec![2, -2], vec![3, -1], vec![3, -3]]);
+
+        // Moore neighbours come in the order of generate_next_unexplored_nearby_cell
+        let moore = cell.get_neighbourhood_coordinates(Neighbourhood::Moore(1));
+        assert_eq!(moore.len(), 9);
+        assert_eq!(moore[0], vec![3, -2]);
+        while cell.has_unexplored_nearby_cell() {
+            cell.generate_next_unexplored_nearby_cell();
+            assert!(moore.contains(&cell.get_nearby_coordinate()));
+        }
+    }
+
 }
\ No newline at end of file
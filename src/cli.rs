@@ -0,0 +1,812 @@
+/*
+* Command line interface
+* Every subcommand takes its options as --name value pairs, so sweeps
+* over sequences and dimensions can be scripted without recompiling
+*/
+
+use cellular_automata_state_search::measure::{self, Measure};
+use cellular_automata_state_search::neighbourhood::{InputNames, Neighbourhood};
+use cellular_automata_state_search::rule::{Action, Actions, Family};
+use cellular_automata_state_search::rule_set::Policy;
+use cellular_automata_state_search::space::{Backend, UpdateMode};
+use cellular_automata_state_search::topology::Topology;
+
+pub const USAGE: &str = "Usage: cellular_automata_state_search <command> [options]
+
+Commands:
+    search            Search for a rule generating the expected sequence
+    emulate           Emulate the rule at a given position of the enumeration
+    enumerate-rules   Print the rules of a dimension in enumeration order
+    count             Count the rules and permutations of each dimension
+    help              Print this message
+
+Options:
+    --dim <n>             Use a single dimension (same as --min-dim n --max-dim n)
+    --min-dim <n>         Smallest dimension to explore (default 1)
+    --max-dim <n>         Largest dimension to explore (default 2)
+    --sequence <a,b,..>   Expected number of set cells per generation (search)
+    --output <file>       Append found rules to the file (search)
+    --save-rule <file>    Save the first found rule to the file, to be emulated with --rule-file.
+                          Only for single rules, not sets of --rules (search)
+    --quiet               Only print results, not every explored rule (search)
+    --no-emulate          Do not emulate the found rule (search)
+    --all                 Collect every rule generating the sequence, implies --no-emulate (search)
+    --threads <n>         Worker threads, 0 for every core (default 0). With more than one,
+                          explored rules are printed every 64 candidates (search)
+    --checkpoint <file>   Periodically save the position of the search to the file (search)
+    --checkpoint-interval <seconds>
+                          Time between checkpoints (default 60) (search)
+    --resume <file>       Continue the search saved in the checkpoint file, with its sequence,
+                          dimensions, --all, --asynchronous, --measure, --neighbourhood, --boundary,
+                          --family, --unset, --else-action, --states, --rules and --policy.
+                          Keeps checkpointing to the same file unless --checkpoint is given (search)
+    --asynchronous        Update cells in place one after the other instead of all at once,
+                          reproducing results from before synchronous updates (search, emulate)
+    --backend <name>      How simulated cells are stored (default sparse): sparse (an entry per
+                          cell) or dense (a bit array growing with the cells, smaller and faster
+                          for dense patterns) (search)
+    --no-dedup            Simulate every candidate, even one computing the same function as an
+                          earlier one, instead of reusing that one's result. Saves the memory of
+                          the results, about 16 MB, without changing the found rules (search)
+    --measure <name>      What is compared with the sequence in each generation (default population):
+                          population, newly-set, bounding-box, perimeter, components:<neighbourhood>,
+                          largest-component:<neighbourhood>, blocks:<neighbourhood> (any block of
+                          the expected size), axis-population:<axis>. Neighbourhoods are moore
+                          and von-neumann (search)
+    --blocks <neighbourhood>
+                          Same as --measure blocks:<neighbourhood> (search)
+    --neighbourhood <moore|von-neumann>
+                          Cells the rules read: the cell and every adjacent cell (3^N, default),
+                          or the cell and the cells differing in a single coordinate (2N + 1)
+                          (search, emulate, enumerate-rules, count)
+    --radius <r>          Radius of the neighbourhood (default 1). Moore reads every cell
+                          differing by at most r in each coordinate, von Neumann by at most
+                          r in total (search, emulate, enumerate-rules, count)
+    --boundary <b,..>     Boundary of each axis, the last one applying to the remaining axes
+                          (default infinite): infinite, dead:<n> (n cells, unset beyond them),
+                          torus:<n> (wrapping around) or reflect:<n> (mirrored at the edges)
+                          (search, emulate)
+    --family <name>       Rules to explore (default tree): tree (conditions of the cells and an
+                          action), elementary (1D rules by Wolfram number), totalistic (from the
+                          number of set cells), outer-totalistic (from the cell and its number
+                          of set neighbours, Life-like) or generations (outer-totalistic, with
+                          --states) (search, emulate, enumerate-rules, count)
+    --unset               Also explore rules unsetting cells, not only setting or flipping them
+                          (search, emulate, enumerate-rules, count)
+    --else-action         Also explore rules with a second action where their condition is
+                          false, which can both set and unset cells (search, emulate,
+                          enumerate-rules, count)
+    --states <k>          Number of states of the cells (default 2), for the tree and generations
+                          families. Conditions test whether cells are in a state, and actions can
+                          move cells to any state (search, emulate, enumerate-rules, count)
+    --rules <k>           Explore sets of k rules updating cells together instead of single
+                          rules (default 1) (search)
+    --policy <name>       How the rules of a set combine (default first-match): first-match
+                          (the first rule acting on a cell), last-match (the last one) or
+                          majority (the next state most rules give) (search)
+    --names <name>        How the inputs of printed rules are written (default index): index (their
+                          position in the tree), offset (the cell read, e.g. (+1,-1), with the
+                          evaluation permutation applied) or compass (N, NE .. in 2D, offsets
+                          otherwise) (search, emulate, enumerate-rules)
+    --index <n>           Position of the rule in the enumeration, from 1. Rules of a family are
+                          at their number + 1 (emulate)
+    --rule <rulestring>   Emulate the Life-like rule, e.g. B3/S23, or generations rule, e.g. B2/S/3,
+                          instead of one at an index (emulate)
+    --rule-file <file>    Emulate the rule saved in the file, e.g. with --save-rule, in its
+                          dimension and neighbourhood (emulate)
+    --condition <expr>    Emulate the rule of the condition, e.g. \"N & !S | (+1,+1)\": inputs are
+                          their index or offset, 0 being the cell, and compass names in 2D,
+                          combined with &, |, ! and parentheses (emulate)
+    --action <name>       Action where the condition holds (default set): set, unset, flip or
+                          a state past 1 with --states (emulate)
+    --else <name>         Action where the condition doesn't hold, the cell being left as it
+                          is without one (emulate)
+    --generations <n>     Print n generations instead of waiting for key presses (emulate)
+    --limit <n>           Stop after n rules (enumerate-rules)";
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct SearchArgs {
+    pub min_dimensions: usize,
+    pub max_dimensions: usize,
+    pub sequence: Vec<usize>,
+    pub output: Option<String>,
+    // File the first found rule is saved to, see Rule::to_text
+    pub save_rule: Option<String>,
+    pub quiet: bool,
+    pub emulate: bool,
+    pub all: bool,
+    pub threads: usize,
+    pub checkpoint: Option<String>,
+    pub checkpoint_interval: u64,
+    pub resume: Option<String>,
+    pub update_mode: UpdateMode,
+    pub backend: Backend,
+    // Whether candidates computing the function of an earlier one are pruned
+    pub dedup: bool,
+    // Name of the measure, see measure::from_name
+    pub measure: String,
+    pub neighbourhood: Neighbourhood,
+    pub topology: Topology,
+    pub family: Family,
+    pub actions: Actions,
+    pub states: usize,
+    // Number of rules of each candidate, and how they combine
+    pub rule_set_len: usize,
+    pub policy: Policy,
+    // How the inputs of found rules are printed
+    pub input_names: InputNames
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EmulateArgs {
+    pub dimension: usize,
+    // Exactly one of the position of the rule, a rulestring, a file saved by Rule::save
+    // and a condition, see Expression::parse. The rule of a file is emulated in its own
+    // dimension and neighbourhood
+    pub index: Option<usize>,
+    pub rulestring: Option<String>,
+    pub rule_file: Option<String>,
+    pub condition: Option<String>,
+    // Actions of the condition
+    pub action: Action,
+    pub else_action: Option<Action>,
+    pub generations: Option<usize>,
+    pub update_mode: UpdateMode,
+    pub neighbourhood: Neighbourhood,
+    pub topology: Topology,
+    pub family: Family,
+    pub actions: Actions,
+    pub states: usize,
+    pub input_names: InputNames
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct EnumerateArgs {
+    pub dimension: usize,
+    pub limit: Option<usize>,
+    pub neighbourhood: Neighbourhood,
+    pub family: Family,
+    pub actions: Actions,
+    pub states: usize,
+    pub input_names: InputNames
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct CountArgs {
+    pub min_dimensions: usize,
+    pub max_dimensions: usize,
+    pub neighbourhood: Neighbourhood,
+    pub family: Family,
+    pub actions: Actions,
+    pub states: usize
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Command {
+    Search(SearchArgs),
+    Emulate(EmulateArgs),
+    EnumerateRules(EnumerateArgs),
+    Count(CountArgs),
+    Help
+}
+
+// Options shared by all commands, each command picks what it needs
+struct Options {
+    min_dimensions: usize,
+    max_dimensions: usize,
+    sequence: Option<Vec<usize>>,
+    output: Option<String>,
+    save_rule: Option<String>,
+    quiet: bool,
+    emulate: bool,
+    all: bool,
+    asynchronous: bool,
+    backend: Backend,
+    dedup: bool,
+    unset: bool,
+    else_action: bool,
+    measure: String,
+    neighbourhood: Neighbourhood,
+    radius: Option<usize>,
+    topology: Topology,
+    family: Family,
+    states: usize,
+    rules: usize,
+    policy: Policy,
+    input_names: InputNames,
+    threads: usize,
+    checkpoint: Option<String>,
+    checkpoint_interval: usize,
+    resume: Option<String>,
+    index: Option<usize>,
+    rulestring: Option<String>,
+    rule_file: Option<String>,
+    condition: Option<String>,
+    action: Option<String>,
+    else_result: Option<String>,
+    generations: Option<usize>,
+    limit: Option<usize>
+}
+
+// args excludes the program name
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    let command = match args.first() {
+        Some(command) => command.as_str(),
+        None => return Ok(Command::Help)
+    };
+
+    if command == "help" || command == "--help" || command == "-h" {
+        return Ok(Command::Help);
+    }
+
+    let options = parse_options(&args[1..])?;
+    let actions = options.actions();
+
+    match command {
+        "search" => {
+            let update_mode = options.update_mode();
+
+            // A resumed search takes its sequence from the checkpoint
+            let sequence = match (options.sequence, &options.resume) {
+                (Some(sequence), _) => sequence,
+                (None, Some(_)) => vec![],
+                (None, None) => return Err("search requires --sequence or --resume".to_string())
+            };
+            if options.save_rule.is_some() && options.rules > 1 {
+                return Err("--save-rule only saves single rules, not sets of --rules".to_string());
+            }
+
+            Ok(Command::Search(SearchArgs {
+                min_dimensions: options.min_dimensions,
+                max_dimensions: options.max_dimensions,
+                sequence,
+                output: options.output,
+                save_rule: options.save_rule,
+                quiet: options.quiet,
+                emulate: options.emulate && !options.all,
+                all: options.all,
+                threads: options.threads,
+                checkpoint: options.checkpoint.or(options.resume.clone()),
+                checkpoint_interval: options.checkpoint_interval as u64,
+                resume: options.resume,
+                update_mode,
+                backend: options.backend,
+                dedup: options.dedup,
+                measure: options.measure,
+                neighbourhood: options.neighbourhood,
+                topology: options.topology,
+                family: options.family,
+                actions,
+                states: options.states,
+                rule_set_len: options.rules,
+                policy: options.policy,
+                input_names: options.input_names
+            }))
+        },
+        "emulate" => {
+            if [options.index.is_some(), options.rulestring.is_some(), options.rule_file.is_some(), options.condition.is_some()].iter().filter(|el| **el).count() != 1 {
+                return Err("emulate requires one of --index, --rule, --rule-file and --condition".to_string());
+            }
+            if options.condition.is_none() && (options.action.is_some() || options.else_result.is_some()) {
+                return Err("--action and --else require --condition".to_string());
+            }
+            let action = match &options.action {
+                Some(action) => parse_action("--action", action, options.states)?,
+                None => Action::Set
+            };
+            let else_action = match &options.else_result {
+                Some(else_action) => Some(parse_action("--else", else_action, options.states)?),
+                None => None
+            };
+            let update_mode = options.update_mode();
+
+            Ok(Command::Emulate(EmulateArgs {
+                // The file has the dimension
+                dimension: if options.rule_file.is_some() { options.min_dimensions } else { single_dimension(&options)? },
+                index: options.index,
+                rulestring: options.rulestring,
+                rule_file: options.rule_file,
+                condition: options.condition,
+                action,
+                else_action,
+                generations: options.generations,
+                update_mode,
+                neighbourhood: options.neighbourhood,
+                topology: options.topology,
+                family: options.family,
+                actions,
+                states: options.states,
+                input_names: options.input_names
+            }))
+        },
+        "enumerate-rules" => {
+            Ok(Command::EnumerateRules(EnumerateArgs {
+                dimension: single_dimension(&options)?,
+                limit: options.limit,
+                neighbourhood: options.neighbourhood,
+                family: options.family,
+                actions,
+                states: options.states,
+                input_names: options.input_names
+            }))
+        },
+        "count" => {
+            Ok(Command::Count(CountArgs {
+                min_dimensions: options.min_dimensions,
+                max_dimensions: options.max_dimensions,
+                neighbourhood: options.neighbourhood,
+                family: options.family,
+                actions,
+                states: options.states
+            }))
+        },
+        _ => Err(format!("Unknown command {}", command))
+    }
+}
+
+impl Options {
+    fn actions(&self) -> Actions {
+        Actions {
+            unset: self.unset,
+            else_action: self.else_action
+        }
+    }
+
+    fn update_mode(&self) -> UpdateMode {
+        if self.asynchronous {
+            UpdateMode::Asynchronous
+        }
+        else {
+            UpdateMode::Synchronous
+        }
+    }
+}
+
+fn single_dimension(options: &Options) -> Result<usize, String> {
+    if options.min_dimensions != options.max_dimensions {
+        return Err("Expected a single dimension, use --dim".to_string());
+    }
+
+    Ok(options.min_dimensions)
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        min_dimensions: 1,
+        max_dimensions: 2,
+        sequence: None,
+        output: None,
+        save_rule: None,
+        quiet: false,
+        emulate: true,
+        all: false,
+        asynchronous: false,
+        backend: Backend::Sparse,
+        dedup: true,
+        unset: false,
+        else_action: false,
+        measure: "population".to_string(),
+        neighbourhood: Neighbourhood::Moore(1),
+        radius: None,
+        topology: Topology::infinite(),
+        family: Family::Tree,
+        states: 2,
+        rules: 1,
+        policy: Policy::FirstMatch,
+        input_names: InputNames::Index,
+        threads: 0,
+        checkpoint: None,
+        checkpoint_interval: 60,
+        resume: None,
+        index: None,
+        rulestring: None,
+        rule_file: None,
+        condition: None,
+        action: None,
+        else_result: None,
+        generations: None,
+        limit: None
+    };
+
+    let mut it = args.iter();
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "--quiet" => options.quiet = true,
+            "--no-emulate" => options.emulate = false,
+            "--all" => options.all = true,
+            "--asynchronous" => options.asynchronous = true,
+            "--no-dedup" => options.dedup = false,
+            "--unset" => options.unset = true,
+            "--else-action" => options.else_action = true,
+            _ => {
+                let value = match it.next() {
+                    Some(value) => value,
+                    None => return Err(format!("Missing value for {}", arg))
+                };
+
+                match arg.as_str() {
+                    "--dim" => {
+                        options.min_dimensions = parse_number(arg, value)?;
+                        options.max_dimensions = options.min_dimensions;
+                    },
+                    "--min-dim" => options.min_dimensions = parse_number(arg, value)?,
+                    "--max-dim" => options.max_dimensions = parse_number(arg, value)?,
+                    "--sequence" => options.sequence = Some(parse_sequence(value)?),
+                    "--output" => options.output = Some(value.clone()),
+                    "--save-rule" => options.save_rule = Some(value.clone()),
+                    "--threads" => options.threads = parse_number(arg, value)?,
+                    "--checkpoint" => options.checkpoint = Some(value.clone()),
+                    "--checkpoint-interval" => options.checkpoint_interval = parse_number(arg, value)?,
+                    "--resume" => options.resume = Some(value.clone()),
+                    "--backend" => options.backend = match Backend::from_name(value) {
+                        Some(backend) => backend,
+                        None => return Err(format!("Invalid value {} for {}", value, arg))
+                    },
+                    "--measure" => options.measure = measure::from_name(value)?.name(),
+                    "--blocks" => options.measure = match Neighbourhood::from_name(value) {
+                        Some(neighbourhood) => measure::BlockSizes(neighbourhood).name(),
+                        None => return Err(format!("Invalid value {} for {}", value, arg))
+                    },
+                    "--neighbourhood" => options.neighbourhood = match Neighbourhood::from_name(value) {
+                        Some(neighbourhood) => neighbourhood,
+                        None => return Err(format!("Invalid value {} for {}", value, arg))
+                    },
+                    "--radius" => match parse_number(arg, value)? {
+                        0 => return Err(format!("Invalid value {} for {}", value, arg)),
+                        radius => options.radius = Some(radius)
+                    },
+                    "--boundary" => options.topology = match Topology::from_name(value) {
+                        Some(topology) => topology,
+                        None => return Err(format!("Invalid value {} for {}", value, arg))
+                    },
+                    "--family" => options.family = match Family::from_name(value) {
+                        Some(family) => family,
+                        None => return Err(format!("Invalid value {} for {}", value, arg))
+                    },
+                    "--states" => options.states = parse_number(arg, value)?,
+                    "--rules" => match parse_number(arg, value)? {
+                        0 => return Err(format!("Invalid value {} for {}", value, arg)),
+                        rules => options.rules = rules
+                    },
+                    "--policy" => options.policy = match Policy::from_name(value) {
+                        Some(policy) => policy,
+                        None => return Err(format!("Invalid value {} for {}", value, arg))
+                    },
+                    "--names" => options.input_names = match InputNames::from_name(value) {
+                        Some(input_names) => input_names,
+                        None => return Err(format!("Invalid value {} for {}", value, arg))
+                    },
+                    "--index" => options.index = Some(parse_number(arg, value)?),
+                    "--rule" => options.rulestring = Some(value.clone()),
+                    "--rule-file" => options.rule_file = Some(value.clone()),
+                    "--condition" => options.condition = Some(value.clone()),
+                    "--action" => options.action = Some(value.clone()),
+                    "--else" => options.else_result = Some(value.clone()),
+                    "--generations" => options.generations = Some(parse_number(arg, value)?),
+                    "--limit" => options.limit = Some(parse_number(arg, value)?),
+                    _ => return Err(format!("Unknown option {}", arg))
+                }
+            }
+        }
+    }
+
+    if let Some(radius) = options.radius {
+        options.neighbourhood = options.neighbourhood.with_radius(radius);
+    }
+
+    if options.min_dimensions == 0 {
+        return Err("Dimensions start at 1".to_string());
+    }
+
+    if options.min_dimensions > options.max_dimensions {
+        return Err(format!("--min-dim {} is greater than --max-dim {}", options.min_dimensions, options.max_dimensions));
+    }
+
+    for dim_len in options.min_dimensions..options.max_dimensions+1 {
+        options.family.supports(dim_len, options.neighbourhood)?;
+    }
+    options.family.supports_states(options.states)?;
+
+    Ok(options)
+}
+
+fn parse_number(option: &str, value: &str) -> Result<usize, String> {
+    value.trim().parse::<usize>().map_err(|_| format!("Invalid value {} for {}", value, option))
+}
+
+// Set, unset, flip, or the number of a state past Set
+fn parse_action(option: &str, value: &str, states: usize) -> Result<Action, String> {
+    match value.trim() {
+        "set" => Ok(Action::Set),
+        "unset" => Ok(Action::Unset),
+        "flip" => Ok(Action::Flip),
+        state => state.parse::<usize>().ok().filter(|state| (2..states).contains(state))
+            .map(|state| Action::State(state as u8))
+            .ok_or(format!("Invalid value {} for {}", value, option))
+    }
+}
+
+fn parse_sequence(value: &str) -> Result<Vec<usize>, String> {
+    let mut sequence = vec![];
+    for el in value.split(',') {
+        sequence.push(parse_number("--sequence", el)?);
+    }
+
+    Ok(sequence)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn to_args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_search() {
+        let command = parse_args(&to_args(&["search", "--min-dim", "1", "--max-dim", "3", "--sequence", "1, 4,9", "--quiet"])).unwrap();
+        assert_eq!(command, Command::Search(SearchArgs {
+            min_dimensions: 1,
+            max_dimensions: 3,
+            sequence: vec![1, 4, 9],
+            output: None,
+            save_rule: None,
+            quiet: true,
+            emulate: true,
+            all: false,
+            threads: 0,
+            checkpoint: None,
+            checkpoint_interval: 60,
+            resume: None,
+            update_mode: UpdateMode::Synchronous,
+            backend: Backend::Sparse,
+            dedup: true,
+            measure: "population".to_string(),
+            neighbourhood: Neighbourhood::Moore(1),
+            topology: Topology::infinite(),
+            family: Family::Tree,
+            actions: Actions::new(),
+            states: 2,
+            rule_set_len: 1,
+            policy: Policy::FirstMatch,
+            input_names: InputNames::Index
+        }));
+
+        let command = parse_args(&to_args(&["search", "--dim", "2", "--sequence", "1,2", "--no-emulate", "--output", "out.txt", "--threads", "1", "--measure", "largest-component:moore"])).unwrap();
+        assert_eq!(command, Command::Search(SearchArgs {
+            min_dimensions: 2,
+            max_dimensions: 2,
+            sequence: vec![1, 2],
+            output: Some("out.txt".to_string()),
+            save_rule: None,
+            quiet: false,
+            emulate: false,
+            all: false,
+            threads: 1,
+            checkpoint: None,
+            checkpoint_interval: 60,
+            resume: None,
+            update_mode: UpdateMode::Synchronous,
+            backend: Backend::Sparse,
+            dedup: true,
+            measure: "largest-component:moore".to_string(),
+            neighbourhood: Neighbourhood::Moore(1),
+            topology: Topology::infinite(),
+            family: Family::Tree,
+            actions: Actions::new(),
+            states: 2,
+            rule_set_len: 1,
+            policy: Policy::FirstMatch,
+            input_names: InputNames::Index
+        }));
+
+        let command = parse_args(&to_args(&["search", "--sequence", "1,2", "--all", "--blocks", "von-neumann"])).unwrap();
+        assert_eq!(command, Command::Search(SearchArgs {
+            min_dimensions: 1,
+            max_dimensions: 2,
+            sequence: vec![1, 2],
+            output: None,
+            save_rule: None,
+            quiet: false,
+            emulate: false,
+            all: true,
+            threads: 0,
+            checkpoint: None,
+            checkpoint_interval: 60,
+            resume: None,
+            update_mode: UpdateMode::Synchronous,
+            backend: Backend::Sparse,
+            dedup: true,
+            measure: "blocks:von-neumann".to_string(),
+            neighbourhood: Neighbourhood::Moore(1),
+            topology: Topology::infinite(),
+            family: Family::Tree,
+            actions: Actions::new(),
+            states: 2,
+            rule_set_len: 1,
+            policy: Policy::FirstMatch,
+            input_names: InputNames::Index
+        }));
+
+        let command = parse_args(&to_args(&["search", "--resume", "cp.txt", "--checkpoint-interval", "5"])).unwrap();
+        assert_eq!(command, Command::Search(SearchArgs {
+            min_dimensions: 1,
+            max_dimensions: 2,
+            sequence: vec![],
+            output: None,
+            save_rule: None,
+            quiet: false,
+            emulate: true,
+            all: false,
+            threads: 0,
+            checkpoint: Some("cp.txt".to_string()),
+            checkpoint_interval: 5,
+            resume: Some("cp.txt".to_string()),
+            update_mode: UpdateMode::Synchronous,
+            backend: Backend::Sparse,
+            dedup: true,
+            measure: "population".to_string(),
+            neighbourhood: Neighbourhood::Moore(1),
+            topology: Topology::infinite(),
+            family: Family::Tree,
+            actions: Actions::new(),
+            states: 2,
+            rule_set_len: 1,
+            policy: Policy::FirstMatch,
+            input_names: InputNames::Index
+        }));
+
+        assert!(parse_args(&to_args(&["search", "--dim", "1"])).is_err());
+        assert!(parse_args(&to_args(&["search", "--sequence", "1,x"])).is_err());
+        assert!(parse_args(&to_args(&["search", "--sequence", "1", "--blocks", "hexagonal"])).is_err());
+        assert!(parse_args(&to_args(&["search", "--sequence", "1", "--measure", "volume"])).is_err());
+        assert!(parse_args(&to_args(&["search", "--min-dim", "3", "--max-dim", "2", "--sequence", "1"])).is_err());
+
+        match parse_args(&to_args(&["search", "--sequence", "1,2", "--rules", "2", "--policy", "majority"])).unwrap() {
+            Command::Search(args) => assert_eq!((args.rule_set_len, args.policy), (2, Policy::Majority)),
+            command => panic!("Parsed {:?}", command)
+        }
+        assert!(parse_args(&to_args(&["search", "--sequence", "1", "--rules", "0"])).is_err());
+        assert!(parse_args(&to_args(&["search", "--sequence", "1", "--policy", "any-match"])).is_err());
+
+        match parse_args(&to_args(&["search", "--sequence", "1,2", "--backend", "dense"])).unwrap() {
+            Command::Search(args) => assert_eq!(args.backend, Backend::Dense),
+            command => panic!("Parsed {:?}", command)
+        }
+        assert!(parse_args(&to_args(&["search", "--sequence", "1", "--backend", "packed"])).is_err());
+        match parse_args(&to_args(&["search", "--sequence", "1,2", "--no-dedup"])).unwrap() {
+            Command::Search(args) => assert!(!args.dedup),
+            command => panic!("Parsed {:?}", command)
+        }
+        assert!(parse_args(&to_args(&["search", "--sequence", "1", "--max-dim", "9"])).is_ok());
+        match parse_args(&to_args(&["search", "--sequence", "1,2", "--boundary", "torus:20,reflect:5"])).unwrap() {
+            Command::Search(args) => assert_eq!(args.topology.name(), "torus:20,reflect:5"),
+            command => panic!("Parsed {:?}", command)
+        }
+        assert!(parse_args(&to_args(&["search", "--sequence", "1", "--boundary", "torus"])).is_err());
+        assert!(parse_args(&to_args(&["count", "--max-dim", "9", "--neighbourhood", "von-neumann"])).is_ok());
+        assert!(parse_args(&to_args(&["search", "--sequence", "1", "--backend", "dense", "--asynchronous"])).is_ok());
+    }
+
+    #[test]
+    fn test_parse_other_commands() {
+        assert_eq!(parse_args(&[]).unwrap(), Command::Help);
+        assert_eq!(parse_args(&to_args(&["help"])).unwrap(), Command::Help);
+
+        assert_eq!(parse_args(&to_args(&["emulate", "--dim", "1", "--index", "7", "--generations", "5", "--asynchronous"])).unwrap(), Command::Emulate(EmulateArgs {
+            dimension: 1,
+            index: Some(7),
+            rulestring: None,
+            rule_file: None,
+            condition: None,
+            action: Action::Set,
+            else_action: None,
+            generations: Some(5),
+            update_mode: UpdateMode::Asynchronous,
+            neighbourhood: Neighbourhood::Moore(1),
+            topology: Topology::infinite(),
+            family: Family::Tree,
+            actions: Actions::new(),
+            states: 2,
+            input_names: InputNames::Index
+        }));
+        assert!(parse_args(&to_args(&["emulate", "--index", "7"])).is_err());
+
+        assert_eq!(parse_args(&to_args(&["emulate", "--dim", "2", "--rule", "B3/S23"])).unwrap(), Command::Emulate(EmulateArgs {
+            dimension: 2,
+            index: None,
+            rulestring: Some("B3/S23".to_string()),
+            rule_file: None,
+            condition: None,
+            action: Action::Set,
+            else_action: None,
+            generations: None,
+            update_mode: UpdateMode::Synchronous,
+            neighbourhood: Neighbourhood::Moore(1),
+            topology: Topology::infinite(),
+            family: Family::Tree,
+            actions: Actions::new(),
+            states: 2,
+            input_names: InputNames::Index
+        }));
+        assert!(parse_args(&to_args(&["emulate", "--dim", "2"])).is_err());
+        assert!(parse_args(&to_args(&["emulate", "--dim", "2", "--index", "7", "--rule", "B3/S23"])).is_err());
+
+        // Without --dim, the dimension being the file's
+        match parse_args(&to_args(&["emulate", "--rule-file", "rule.txt", "--generations", "3"])).unwrap() {
+            Command::Emulate(args) => assert_eq!((args.rule_file, args.generations), (Some("rule.txt".to_string()), Some(3))),
+            command => panic!("Parsed {:?}", command)
+        }
+        assert!(parse_args(&to_args(&["emulate", "--rule-file", "rule.txt", "--rule", "B3/S23"])).is_err());
+
+        match parse_args(&to_args(&["emulate", "--dim", "2", "--condition", "N & !S", "--action", "flip", "--else", "unset"])).unwrap() {
+            Command::Emulate(args) => assert_eq!((args.condition, args.action, args.else_action), (Some("N & !S".to_string()), Action::Flip, Some(Action::Unset))),
+            command => panic!("Parsed {:?}", command)
+        }
+        match parse_args(&to_args(&["emulate", "--dim", "2", "--condition", "0", "--action", "2", "--states", "3"])).unwrap() {
+            Command::Emulate(args) => assert_eq!(args.action, Action::State(2)),
+            command => panic!("Parsed {:?}", command)
+        }
+        assert!(parse_args(&to_args(&["emulate", "--dim", "2", "--condition", "0", "--action", "2"])).is_err());
+        assert!(parse_args(&to_args(&["emulate", "--dim", "2", "--condition", "0", "--else", "keep"])).is_err());
+        assert!(parse_args(&to_args(&["emulate", "--dim", "2", "--index", "3", "--action", "flip"])).is_err());
+        assert!(parse_args(&to_args(&["emulate", "--dim", "2", "--index", "3", "--condition", "0"])).is_err());
+        match parse_args(&to_args(&["emulate", "--dim", "2", "--index", "3", "--names", "compass"])).unwrap() {
+            Command::Emulate(args) => assert_eq!(args.input_names, InputNames::Compass),
+            command => panic!("Parsed {:?}", command)
+        }
+        assert!(parse_args(&to_args(&["enumerate-rules", "--dim", "2", "--names", "octal"])).is_err());
+        match parse_args(&to_args(&["search", "--sequence", "1,2", "--save-rule", "rule.txt"])).unwrap() {
+            Command::Search(args) => assert_eq!(args.save_rule, Some("rule.txt".to_string())),
+            command => panic!("Parsed {:?}", command)
+        }
+        assert!(parse_args(&to_args(&["search", "--sequence", "1,2", "--save-rule", "rule.txt", "--rules", "2"])).is_err());
+
+        assert_eq!(parse_args(&to_args(&["enumerate-rules", "--dim", "2", "--limit", "10", "--neighbourhood", "von-neumann"])).unwrap(), Command::EnumerateRules(EnumerateArgs {
+            dimension: 2,
+            limit: Some(10),
+            neighbourhood: Neighbourhood::VonNeumann(1),
+            family: Family::Tree,
+            actions: Actions::new(),
+            states: 2,
+            input_names: InputNames::Index
+        }));
+
+        assert_eq!(parse_args(&to_args(&["count", "--max-dim", "1", "--radius", "2"])).unwrap(), Command::Count(CountArgs {
+            min_dimensions: 1,
+            max_dimensions: 1,
+            neighbourhood: Neighbourhood::Moore(2),
+            family: Family::Tree,
+            actions: Actions::new(),
+            states: 2
+        }));
+
+        assert!(parse_args(&to_args(&["frobnicate"])).is_err());
+        assert!(parse_args(&to_args(&["count", "--dim"])).is_err());
+        assert!(parse_args(&to_args(&["count", "--neighbourhood", "hexagonal"])).is_err());
+        assert!(parse_args(&to_args(&["count", "--radius", "0"])).is_err());
+
+        assert_eq!(parse_args(&to_args(&["count", "--dim", "2", "--family", "outer-totalistic"])).unwrap(), Command::Count(CountArgs {
+            min_dimensions: 2,
+            max_dimensions: 2,
+            neighbourhood: Neighbourhood::Moore(1),
+            family: Family::OuterTotalistic,
+            actions: Actions::new(),
+            states: 2
+        }));
+        assert!(parse_args(&to_args(&["count", "--family", "hexagonal"])).is_err());
+
+        assert_eq!(parse_args(&to_args(&["count", "--dim", "1", "--unset", "--else-action"])).unwrap(), Command::Count(CountArgs {
+            min_dimensions: 1,
+            max_dimensions: 1,
+            neighbourhood: Neighbourhood::Moore(1),
+            family: Family::Tree,
+            actions: Actions { unset: true, else_action: true },
+            states: 2
+        }));
+        // Elementary rules are 1D, and 4D Moore neighbourhoods have 2^162 outer-totalistic rules
+        assert!(parse_args(&to_args(&["count", "--family", "elementary"])).is_err());
+        assert!(parse_args(&to_args(&["count", "--dim", "4", "--family", "outer-totalistic"])).is_err());
+    }
+}
@@ -0,0 +1,143 @@
+/*
+* Configurations
+*
+* The set cells of a space and their states, up to a translation: the cells
+* are moved so that the lowest corner of their bounding box is the origin, then
+* sorted by their coordinates. Generations differing by a shift, like those of a
+* glider, have the same configuration, so configurations can be compared, hashed
+* and deduplicated.
+*
+* The symmetric configuration is also the same for patterns rotated or
+* reflected: it's the least configuration of the images of the pattern by every
+* permutation of the axes with every choice of their directions, 2 in 1D, 8 in 2D,
+* 48 in 3D and 2^n n! in n dimensions.
+*/
+
+use crate::cell::CellValue;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Configuration {
+    cells: Vec<(Vec<i32>, CellValue)>
+}
+
+impl Configuration {
+    // Set cells among the cells, moved along every axis
+    pub fn new(cells: impl IntoIterator<Item = (Vec<i32>, CellValue)>) -> Self {
+        Configuration::along_axes(cells, |_| true)
+    }
+
+    // Set cells among the cells, only moved along the axes accepted, so that their lowest
+    // coordinate along each is 0. The other axes are kept as they are
+    pub fn along_axes(cells: impl IntoIterator<Item = (Vec<i32>, CellValue)>, moved: impl Fn(usize) -> bool) -> Self {
+        let mut cells: Vec<(Vec<i32>, CellValue)> = cells.into_iter().filter(|(_, value)| value.is_set()).collect();
+        let dim_len = cells.first().map_or(0, |(coordinates, _)| coordinates.len());
+        for axis in (0..dim_len).filter(|axis| moved(*axis)) {
+            let min = cells.iter().map(|(coordinates, _)| coordinates[axis]).min().unwrap();
+            for (coordinates, _) in cells.iter_mut() {
+                coordinates[axis] -= min;
+            }
+        }
+        cells.sort_unstable();
+        Configuration { cells }
+    }
+
+    // Least configuration of the rotations and reflections of this one
+    pub fn symmetric(&self) -> Configuration {
+        let Some((first, _)) = self.cells.first() else {
+            return self.clone();
+        };
+
+        let mut least = self.clone();
+        for axes in permutations(first.len()) {
+            for directions in 0..1u32 << axes.len() {
+                // Axis i of the image is axis axes[i], reversed when bit i of directions is set
+                let image = Configuration::new(self.cells.iter().map(|(coordinates, value)| {
+                    let coordinates = axes.iter().enumerate().map(|(i, axis)| {
+                        if directions >> i & 1 == 1 { -coordinates[*axis] } else { coordinates[*axis] }
+                    }).collect();
+                    (coordinates, *value)
+                }));
+                least = least.min(image);
+            }
+        }
+        least
+    }
+
+    pub fn cells(&self) -> &[(Vec<i32>, CellValue)] {
+        &self.cells
+    }
+
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+}
+
+// Every order of 0 .. len
+fn permutations(len: usize) -> Vec<Vec<usize>> {
+    if len == 0 {
+        return vec![vec![]];
+    }
+    permutations(len - 1).into_iter().flat_map(|permutation| {
+        (0..len).map(move |i| {
+            let mut permutation = permutation.clone();
+            permutation.insert(i, len - 1);
+            permutation
+        })
+    }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn configuration(coordinates: &[Vec<i32>]) -> Configuration {
+        Configuration::new(coordinates.iter().map(|coordinates| (coordinates.clone(), CellValue::Set)))
+    }
+
+    #[test]
+    fn test_translation() {
+        let glider = vec![vec![1, 0], vec![2, 1], vec![0, 2], vec![1, 2], vec![2, 2]];
+        let moved: Vec<Vec<i32>> = glider.iter().map(|c| vec![c[0] - 9, c[1] + 4]).collect();
+        assert_eq!(configuration(&glider), configuration(&moved));
+        assert_eq!(configuration(&moved).cells()[0], (vec![0, 2], CellValue::Set));
+
+        // Unset cells aren't part of it, states are
+        let mut cells: Vec<(Vec<i32>, CellValue)> = glider.iter().map(|c| (c.clone(), CellValue::Set)).collect();
+        cells.push((vec![-5, -5], CellValue::Unset));
+        assert_eq!(Configuration::new(cells.clone()), configuration(&glider));
+        cells[0].1 = CellValue::new(2);
+        assert_ne!(Configuration::new(cells), configuration(&glider));
+
+        // Only along the axes given
+        let along = |coordinates: &[Vec<i32>]| Configuration::along_axes(coordinates.iter().map(|c| (c.clone(), CellValue::Set)), |axis| axis == 0);
+        assert_ne!(along(&glider), along(&moved));
+        assert_eq!(along(&moved).cells()[0], (vec![0, 6], CellValue::Set));
+
+        assert_eq!(configuration(&[]).len(), 0);
+        assert_eq!(configuration(&[]).symmetric(), configuration(&[]));
+    }
+
+    #[test]
+    fn test_symmetry() {
+        // The 4 phases of a glider going each diagonal are the same under symmetry
+        let glider = [vec![1, 0], vec![2, 1], vec![0, 2], vec![1, 2], vec![2, 2]];
+        let images: Vec<Configuration> = [[1, 1], [-1, 1], [1, -1], [-1, -1]].iter()
+            .map(|[x, y]| configuration(&glider.iter().map(|c| vec![x * c[0], y * c[1]]).collect::<Vec<Vec<i32>>>()))
+            .collect();
+        assert!(images[1..].iter().all(|image| *image != images[0]));
+        assert!(images.iter().all(|image| image.symmetric() == images[0].symmetric()));
+
+        let transposed: Vec<Vec<i32>> = glider.iter().map(|c| vec![c[1], c[0]]).collect();
+        assert_eq!(configuration(&transposed).symmetric(), images[0].symmetric());
+
+        // A blinker has 2 orientations, an L tromino 4
+        let horizontal = configuration(&[vec![0, 0], vec![1, 0], vec![2, 0]]);
+        let vertical = configuration(&[vec![0, 0], vec![0, 1], vec![0, 2]]);
+        assert_eq!(horizontal.symmetric(), vertical.symmetric());
+        let tromino = configuration(&[vec![0, 0], vec![1, 0], vec![0, 1]]);
+        assert_ne!(tromino.symmetric(), horizontal.symmetric());
+
+        assert_eq!(permutations(3).len(), 6);
+        assert_eq!(permutations(1), vec![vec![0]]);
+    }
+}
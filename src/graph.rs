@@ -1 +1,4 @@
-pub mod op_node;
\ No newline at end of file
+pub mod expression;
+pub mod op_node;
+pub mod sum_of_products;
+pub mod truth_table;
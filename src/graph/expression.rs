@@ -0,0 +1,233 @@
+/*
+* Hand-written conditions
+*
+* An Expression is a condition of any shape over the inputs of a rule, which
+* can read some inputs several times and others not at all, unlike the OpNode
+* trees of the enumeration. It's parsed from infix text:
+*
+*   (0 & !4) | (3 & 5)
+*   N & !S | (+1,+1)
+*
+* with & binding tighter than |, ! negating what follows it, and parentheses
+* grouping. Inputs are their index, 0 being the cell itself, or the offset of
+* the cell they read, see neighbourhood::offset_from_name. An offset in
+* parentheses has a comma or a sign, so "(1)" is input 1 and "(+1)" the cell
+* at +1 in 1D.
+*/
+
+use std::fmt;
+
+use crate::neighbourhood::{self, Neighbourhood};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expression {
+    // Value of an input, 0 being the cell itself
+    Input(usize),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Input(usize),
+    And,
+    Or,
+    Not,
+    Open,
+    Close
+}
+
+impl Expression {
+    // The inputs of the rule are those of the neighbourhood in the dimension
+    pub fn parse(text: &str, dim_len: usize, neighbourhood: Neighbourhood) -> Result<Self, String> {
+        let tokens = tokenize(text, dim_len, neighbourhood)?;
+        let mut position = 0;
+        let expression = parse_or(&tokens, &mut position)?;
+        if position < tokens.len() {
+            return Err(format!("Unexpected {:?} in {}", tokens[position], text));
+        }
+        Ok(expression)
+    }
+
+    pub fn evaluate(&self, values: &[bool]) -> bool {
+        match self {
+            Expression::Input(i) => values[*i],
+            Expression::Not(expression) => !expression.evaluate(values),
+            Expression::And(left, right) => left.evaluate(values) && right.evaluate(values),
+            Expression::Or(left, right) => left.evaluate(values) || right.evaluate(values)
+        }
+    }
+
+    // Written with the name of each input instead of its index
+    pub fn to_named_string(&self, name: &dyn Fn(usize) -> String) -> String {
+        match self {
+            Expression::Input(i) => name(*i),
+            Expression::Not(expression) => format!("!{}", expression.to_named_string(name)),
+            Expression::And(left, right) => format!("( {} & {} )", left.to_named_string(name), right.to_named_string(name)),
+            Expression::Or(left, right) => format!("( {} | {} )", left.to_named_string(name), right.to_named_string(name))
+        }
+    }
+
+    // Inputs the expression reads, in order
+    pub fn inputs(&self) -> Vec<usize> {
+        let mut inputs = match self {
+            Expression::Input(i) => vec![*i],
+            Expression::Not(expression) => expression.inputs(),
+            Expression::And(left, right) | Expression::Or(left, right) => [left.inputs(), right.inputs()].concat()
+        };
+        inputs.sort_unstable();
+        inputs.dedup();
+        inputs
+    }
+}
+
+fn tokenize(text: &str, dim_len: usize, neighbourhood: Neighbourhood) -> Result<Vec<Token>, String> {
+    let input = |offset: Option<Vec<i32>>, name: &str| offset
+        .and_then(|offset| neighbourhood.input_index(dim_len, &offset))
+        .map(Token::Input)
+        .ok_or(format!("{} isn't a cell of the {} neighbourhood in {}D", name, neighbourhood.name(), dim_len));
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let start = i;
+        i += 1;
+        match c {
+            _ if c.is_whitespace() => {},
+            '&' => tokens.push(Token::And),
+            '|' => tokens.push(Token::Or),
+            '!' => tokens.push(Token::Not),
+            ')' => tokens.push(Token::Close),
+            '(' => {
+                // An offset, or the start of a group
+                let end = chars[start..].iter().position(|c| *c == ')').map(|end| start + end);
+                let offset = end.map(|end| chars[start..=end].iter().collect::<String>())
+                    .filter(|offset| offset[1..offset.len() - 1].chars().all(|c| "+-,0123456789 ".contains(c)) && offset.contains([',', '+', '-']));
+                match (offset, end) {
+                    (Some(offset), Some(end)) => {
+                        tokens.push(input(neighbourhood::offset_from_name(dim_len, &offset), &offset)?);
+                        i = end + 1;
+                    },
+                    _ => tokens.push(Token::Open)
+                }
+            },
+            _ if c.is_ascii_alphanumeric() => {
+                while i < chars.len() && chars[i].is_ascii_alphanumeric() {
+                    i += 1;
+                }
+                let name: String = chars[start..i].iter().collect();
+                match name.parse::<usize>() {
+                    Ok(index) if index < neighbourhood.len(dim_len) => tokens.push(Token::Input(index)),
+                    Ok(index) => return Err(format!("Input {} is past the {} inputs of the {} neighbourhood in {}D", index, neighbourhood.len(dim_len), neighbourhood.name(), dim_len)),
+                    Err(_) => tokens.push(input(neighbourhood::offset_from_name(dim_len, &name), &name)?)
+                }
+            },
+            _ => return Err(format!("Unexpected {} in {}", c, text))
+        }
+    }
+    Ok(tokens)
+}
+
+// Operands of |
+fn parse_or(tokens: &[Token], position: &mut usize) -> Result<Expression, String> {
+    let mut expression = parse_and(tokens, position)?;
+    while tokens.get(*position) == Some(&Token::Or) {
+        *position += 1;
+        expression = Expression::Or(Box::new(expression), Box::new(parse_and(tokens, position)?));
+    }
+    Ok(expression)
+}
+
+// Operands of &
+fn parse_and(tokens: &[Token], position: &mut usize) -> Result<Expression, String> {
+    let mut expression = parse_operand(tokens, position)?;
+    while tokens.get(*position) == Some(&Token::And) {
+        *position += 1;
+        expression = Expression::And(Box::new(expression), Box::new(parse_operand(tokens, position)?));
+    }
+    Ok(expression)
+}
+
+fn parse_operand(tokens: &[Token], position: &mut usize) -> Result<Expression, String> {
+    let token = tokens.get(*position).ok_or("Expression ended early")?;
+    *position += 1;
+    match token {
+        Token::Input(i) => Ok(Expression::Input(*i)),
+        Token::Not => Ok(Expression::Not(Box::new(parse_operand(tokens, position)?))),
+        Token::Open => {
+            let expression = parse_or(tokens, position)?;
+            if tokens.get(*position) != Some(&Token::Close) {
+                return Err("Missing )".to_string());
+            }
+            *position += 1;
+            Ok(expression)
+        },
+        token => Err(format!("Unexpected {:?}", token))
+    }
+}
+
+// Written as OpNode trees are, so an expression reads back as itself
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_named_string(&|i| i.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let parse = |text: &str| Expression::parse(text, 2, Neighbourhood::Moore(1));
+        let expression = parse("(0 & !4) | (3 & 5)").unwrap();
+        assert_eq!(expression.to_string(), "( ( 0 & !4 ) | ( 3 & 5 ) )");
+        assert_eq!(expression.inputs(), vec![0, 3, 4, 5]);
+        assert_eq!(parse(&expression.to_string()).unwrap(), expression);
+
+        // & binds tighter than |, and both go left to right
+        assert_eq!(parse("0 | 1 & 2 | 3").unwrap().to_string(), "( ( 0 | ( 1 & 2 ) ) | 3 )");
+        assert_eq!(parse("!!(1 | 2)").unwrap().to_string(), "!!( 1 | 2 )");
+
+        let mut values = vec![false; 9];
+        assert!(!expression.evaluate(&values));
+        values[0] = true;
+        assert!(expression.evaluate(&values));
+        values[4] = true;
+        assert!(!expression.evaluate(&values));
+        (values[3], values[5]) = (true, true);
+        assert!(expression.evaluate(&values));
+
+        for text in ["", "0 &", "(0 | 1", "0 1", "0 & | 1", "9", "0 ^ 1", ")", "UP"] {
+            assert!(parse(text).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_offsets() {
+        // Offsets and compass names are the inputs reading those cells
+        let inputs = Neighbourhood::Moore(1).inputs(2);
+        let parse = |text: &str| Expression::parse(text, 2, Neighbourhood::Moore(1)).unwrap();
+        let index = |offset: &[i32]| Expression::Input(inputs.iter().position(|input| input == offset).unwrap());
+        assert_eq!(parse("N"), index(&[-1, 0]));
+        assert_eq!(parse("(+1,-1)"), index(&[1, -1]));
+        assert_eq!(parse("( 0, 1 )"), index(&[0, 1]));
+        assert_eq!(parse("C & !SW"), Expression::And(Box::new(Expression::Input(0)), Box::new(Expression::Not(Box::new(index(&[1, -1]))))));
+        assert_eq!(parse("(NE | (-1,-1))").to_string(), parse("NE | NW").to_string());
+
+        // In 1D, a number in parentheses is an input unless it has a sign
+        let parse_1d = |text: &str| Expression::parse(text, 1, Neighbourhood::Moore(2));
+        assert_eq!(parse_1d("(2)").unwrap(), Expression::Input(2));
+        assert_eq!(parse_1d("(+2)").unwrap(), Expression::Input(3));
+        assert_eq!(parse_1d("((-1) & 0)").unwrap().to_string(), "( 2 & 0 )");
+
+        for text in ["(+2,0)", "(+1)", "N & NNE"] {
+            assert!(Expression::parse(text, 2, Neighbourhood::Moore(1)).is_err(), "{}", text);
+        }
+        assert!(parse_1d("N").is_err());
+        assert!(Expression::parse("NE", 2, Neighbourhood::VonNeumann(1)).is_err());
+    }
+}
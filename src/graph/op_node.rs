@@ -1,4 +1,7 @@
-use crate::permutation::{set_permuter::SetPermuter, vector_permuter::VectorPermuter};
+use std::fmt;
+
+use crate::graph::expression::Expression;
+use crate::permutation::set_permuter::SetPermuter;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Op {
@@ -35,18 +38,19 @@ impl OpNode {
             operation: Op::None,
             left_child: None,
             right_child: None,
-            start_index: start_index,
-            end_index: end_index,
+            start_index,
+            end_index,
             split_index: 0,
             eval_permutation: SetPermuter::new(vec![])
         };
         node.reset(start_index, end_index, true);
         node.reset_permuter();
 
-        return node;
+        node
     }
 
-    fn reset_permuter(&mut self) {
+    // Back to the first evaluation permutation of the tree
+    pub fn reset_permuter(&mut self) {
         self.eval_permutation.reset(self.get_clustered_variables());
     }
 
@@ -68,35 +72,26 @@ impl OpNode {
     }
 
     pub fn print(&self) {
-        // Leaf node
-        if self.start_index == self.end_index {
-            if self.operation == Op::Not {
-                
-                print!("!");
-            }
-            print!("{}", self.start_index);
-            return;
-        }
-
-        // Parent node
-        print!("( ");
-
-        if self.left_child != None {
-            self.left_child.as_ref().unwrap().print();
-        }
+        print!("{}", self);
+    }
 
-        if self.operation == Op::And {
-            print!(" & ");
-        }
-        else if self.operation == Op::Or {
-            print!(" | ");
-        }
+    // The tree with the evaluation permutation applied, so each leaf is the input it
+    // reads rather than its position. Only called on root node
+    pub fn to_expression(&self) -> Expression {
+        self.permuted_expression(&self.eval_permutation.get_vector())
+    }
 
-        if self.right_child != None {
-            self.right_child.as_ref().unwrap().print();
+    // Leaf i reads input inputs[i]
+    fn permuted_expression(&self, inputs: &[usize]) -> Expression {
+        // Leaf node
+        if self.start_index == self.end_index {
+            let input = Expression::Input(inputs[self.start_index]);
+            return if self.operation == Op::Not { Expression::Not(Box::new(input)) } else { input };
         }
 
-        print!(" )");
+        let left = Box::new(self.left_child.as_ref().unwrap().permuted_expression(inputs));
+        let right = Box::new(self.right_child.as_ref().unwrap().permuted_expression(inputs));
+        if self.operation == Op::And { Expression::And(left, right) } else { Expression::Or(left, right) }
     }
 
     pub fn len(&self) -> usize {
@@ -110,10 +105,10 @@ impl OpNode {
             return self.operation == Op::None;
         }
 
-        (self.left_child != None && self.left_child.as_ref().unwrap().has_next())
-        || (self.right_child != None && self.right_child.as_ref().unwrap().has_next())
+        (self.left_child.is_some() && self.left_child.as_ref().unwrap().has_next())
+        || (self.right_child.is_some() && self.right_child.as_ref().unwrap().has_next())
         || self.operation == Op::And
-        || self.split_index < (self.end_index + 1)/2 - 1
+        || self.split_index < self.end_index.div_ceil(2) - 1
     }
 
     fn reset(&mut self, start_index: usize, end_index: usize, reset_split: bool) {  
@@ -164,8 +159,7 @@ impl OpNode {
         let mut same_op_children: Vec<usize> = vec![];
         let mut result : Vec<Vec<usize>> = vec![];
 
-        if self.left_child != None {
-            let left_node = self.left_child.as_ref().unwrap().as_ref();
+        if let Some(left_node) = &self.left_child {
 
             let mut left_cluster = left_node.get_clustered_variables();
             if left_node.operation == self.operation || left_node.start_index == left_node.end_index {
@@ -175,13 +169,12 @@ impl OpNode {
                 left_cluster = left_cluster.as_slice()[0..left_cluster.len() - 1].to_vec();
             }
 
-            if left_cluster.len() > 0 {
+            if !left_cluster.is_empty() {
                 result.append(&mut left_cluster);
             }
         }
 
-        if self.right_child != None {
-            let right_node = self.right_child.as_ref().unwrap().as_ref();
+        if let Some(right_node) = &self.right_child {
             
             let mut right_cluster = right_node.get_clustered_variables();
             if right_node.operation == self.operation || right_node.start_index == right_node.end_index {
@@ -189,12 +182,12 @@ impl OpNode {
                 right_cluster = right_cluster.as_slice()[0..right_cluster.len()-1].to_vec();
             }
 
-            if right_cluster.len() > 0 {
+            if !right_cluster.is_empty() {
                 result.append(&mut right_cluster);
             }
         }
 
-        if same_op_children. len() > 0 {
+        if !same_op_children.is_empty() {
             result.push(same_op_children);
         }
         
@@ -203,7 +196,7 @@ impl OpNode {
 
     // value has corresponding (T/F)
     pub fn evaluate(&self, values: &Vec<bool>, apply_permutation_at_current_level: bool) -> bool {
-        if values.len() == 0 {
+        if values.is_empty() {
             panic!("Invalid input");
         }
         
@@ -212,10 +205,9 @@ impl OpNode {
         }
 
 
-        let evaluation_values: &Vec<bool>;
         let mut permuted_values: Vec<bool> = vec![];
 
-        if apply_permutation_at_current_level {
+        let evaluation_values: &Vec<bool> = if apply_permutation_at_current_level {
             // Apply permutation
             // WARN: memory leak, avoid clone?
             let v = self.eval_permutation.get_vector();
@@ -223,11 +215,11 @@ impl OpNode {
                 permuted_values.push(values[ii]);
             }
 
-            evaluation_values = &permuted_values;
+            &permuted_values
         }
         else {
-            evaluation_values = values;
-        }
+            values
+        };
 
         // Leaf node
         if self.start_index == self.end_index {
@@ -241,8 +233,8 @@ impl OpNode {
         }
 
         // Parent node
-        let left_eval = self.left_child.as_ref().unwrap().evaluate(&evaluation_values, false);
-        let right_eval = self.right_child.as_ref().unwrap().evaluate(&evaluation_values, false);
+        let left_eval = self.left_child.as_ref().unwrap().evaluate(evaluation_values, false);
+        let right_eval = self.right_child.as_ref().unwrap().evaluate(evaluation_values, false);
         
         if self.operation == Op::And {
             return left_eval && right_eval;
@@ -268,7 +260,7 @@ impl OpNode {
         // Non-leaf node
 
         // Check if left_child can be restructured
-        if self.left_child != None && self.left_child.as_ref().unwrap().has_next() {
+        if self.left_child.is_some() && self.left_child.as_ref().unwrap().has_next() {
             self.left_child.as_mut().unwrap().generate_next();
             self.reset_permuter();
             return;
@@ -276,7 +268,7 @@ impl OpNode {
         
         // If left_child cannot be, check if right_child can be
         // If right_child is being restructured, reset left_child to initial state
-        if self.right_child != None && self.right_child.as_mut().unwrap().has_next() {
+        if self.right_child.is_some() && self.right_child.as_mut().unwrap().has_next() {
             self.right_child.as_mut().unwrap().generate_next();
             self.left_child.as_mut().unwrap().reset(self.start_index, self.split_index, true);
             self.reset_permuter();
@@ -295,7 +287,7 @@ impl OpNode {
 
         // In-order to avoid generating symmetric trees, we restructure till the middle element of array.
         // All structures generated after half are anyways handled by permuting input value array
-        if self.split_index >= (self.end_index + 1)/2 {
+        if self.split_index >= self.end_index.div_ceil(2) {
             panic!("All possible trees generated");
         }
 
@@ -314,8 +306,136 @@ impl OpNode {
 
         self.reset_permuter();
     }
+
+    /*
+     * Enumeration state of the tree, written in pre-order:
+     * a leaf is "v" (None) or "!" (Not), a parent is "&" or "|" followed by
+     * its split index, then its left and right children.
+     * So ( 0 & ( !1 | 2 ) ) is "& 0 v | 1 ! v"
+     *
+     * Evaluation permutation is not included, see SetPermuter::get_state
+     */
+    pub fn get_state(&self) -> String {
+        let mut tokens: Vec<String> = vec![];
+        self.write_state(&mut tokens);
+        tokens.join(" ")
+    }
+
+    fn write_state(&self, tokens: &mut Vec<String>) {
+        // Leaf node
+        if self.start_index == self.end_index {
+            if self.operation == Op::Not {
+                tokens.push("!".to_string());
+            }
+            else {
+                tokens.push("v".to_string());
+            }
+            return;
+        }
+
+        if self.operation == Op::Or {
+            tokens.push("|".to_string());
+        }
+        else {
+            tokens.push("&".to_string());
+        }
+        tokens.push(self.split_index.to_string());
+
+        if let Some(left_child) = &self.left_child {
+            left_child.write_state(tokens);
+        }
+        if let Some(right_child) = &self.right_child {
+            right_child.write_state(tokens);
+        }
+    }
+
+    pub fn from_state(start_index: usize, end_index: usize, state: &str) -> Result<Self, String> {
+        let mut tokens = state.split_whitespace();
+        let node = OpNode::read_state(start_index, end_index, &mut tokens)?;
+
+        if let Some(token) = tokens.next() {
+            return Err(format!("Unexpected {} after the end of the tree", token));
+        }
+
+        Ok(node)
+    }
+
+    fn read_state<'a>(start_index: usize, end_index: usize, tokens: &mut impl Iterator<Item = &'a str>) -> Result<Self, String> {
+        let token = tokens.next().ok_or("Tree ended early")?;
+
+        let mut node = OpNode {
+            operation: Op::None,
+            left_child: None,
+            right_child: None,
+            start_index,
+            end_index,
+            split_index: start_index,
+            eval_permutation: SetPermuter::new(vec![])
+        };
+
+        if start_index == end_index {
+            node.operation = match token {
+                "v" => Op::None,
+                "!" => Op::Not,
+                _ => return Err(format!("Expected a leaf for index {}, found {}", start_index, token))
+            };
+        }
+        else {
+            node.operation = match token {
+                "&" => Op::And,
+                "|" => Op::Or,
+                _ => return Err(format!("Expected an operation for indices {} to {}, found {}", start_index, end_index, token))
+            };
+
+            let split_token = tokens.next().ok_or("Tree ended early")?;
+            node.split_index = split_token.parse::<usize>()
+                .map_err(|_| format!("Invalid split index {}", split_token))?;
+            if node.split_index < start_index || node.split_index >= end_index {
+                return Err(format!("Split index {} outside of {} to {}", node.split_index, start_index, end_index));
+            }
+
+            node.left_child = Some(Box::new(OpNode::read_state(start_index, node.split_index, tokens)?));
+            node.right_child = Some(Box::new(OpNode::read_state(node.split_index + 1, end_index, tokens)?));
+        }
+
+        node.reset_permuter();
+        Ok(node)
+    }
 }
 
+impl fmt::Display for OpNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Leaf node
+        if self.start_index == self.end_index {
+            if self.operation == Op::Not {
+                write!(f, "!")?;
+            }
+            return write!(f, "{}", self.start_index);
+        }
+
+        // Parent node
+        write!(f, "( ")?;
+
+        if let Some(left_child) = &self.left_child {
+            write!(f, "{}", left_child)?;
+        }
+
+        if self.operation == Op::And {
+            write!(f, " & ")?;
+        }
+        else if self.operation == Op::Or {
+            write!(f, " | ")?;
+        }
+
+        if let Some(right_child) = &self.right_child {
+            write!(f, "{}", right_child)?;
+        }
+
+        write!(f, " )")
+    }
+}
+
+#[cfg(test)]
 mod test {
     use super::*;
 
@@ -461,68 +581,68 @@ mod test {
     fn test_op_node_eval() {
         let mut leaf_node = OpNode::new(0, 0);
 
-        assert_eq!(leaf_node.evaluate(&vec![true], true), true);
+        assert!(leaf_node.evaluate(&vec![true], true));
         leaf_node.generate_next();
-        assert_eq!(leaf_node.evaluate(&vec![true], true), false);
+        assert!(!leaf_node.evaluate(&vec![true], true));
 
         let mut two_var_node = OpNode::new(0, 1);
 
         // None And None
-        assert_eq!(two_var_node.evaluate(&vec![true, true], true), true);
-        assert_eq!(two_var_node.evaluate(&vec![true, false], true), false);
-        assert_eq!(two_var_node.evaluate(&vec![false, true], true), false);
-        assert_eq!(two_var_node.evaluate(&vec![false, false], true), false);
+        assert!(two_var_node.evaluate(&vec![true, true], true));
+        assert!(!two_var_node.evaluate(&vec![true, false], true));
+        assert!(!two_var_node.evaluate(&vec![false, true], true));
+        assert!(!two_var_node.evaluate(&vec![false, false], true));
 
         // Not AND None
         two_var_node.generate_next();
-        assert_eq!(two_var_node.evaluate(&vec![true, true], true), false);
-        assert_eq!(two_var_node.evaluate(&vec![true, false], true), false);
-        assert_eq!(two_var_node.evaluate(&vec![false, true], true), true);
-        assert_eq!(two_var_node.evaluate(&vec![false, false], true), false);
+        assert!(!two_var_node.evaluate(&vec![true, true], true));
+        assert!(!two_var_node.evaluate(&vec![true, false], true));
+        assert!(two_var_node.evaluate(&vec![false, true], true));
+        assert!(!two_var_node.evaluate(&vec![false, false], true));
 
         // None AND Not
         two_var_node.generate_next();
-        assert_eq!(two_var_node.evaluate(&vec![true, true], true), false);
-        assert_eq!(two_var_node.evaluate(&vec![true, false], true), true);
-        assert_eq!(two_var_node.evaluate(&vec![false, true], true), false);
-        assert_eq!(two_var_node.evaluate(&vec![false, false], true), false);
+        assert!(!two_var_node.evaluate(&vec![true, true], true));
+        assert!(two_var_node.evaluate(&vec![true, false], true));
+        assert!(!two_var_node.evaluate(&vec![false, true], true));
+        assert!(!two_var_node.evaluate(&vec![false, false], true));
         
         // Not AND Not
         two_var_node.generate_next();
-        assert_eq!(two_var_node.evaluate(&vec![true, true], true), false);
-        assert_eq!(two_var_node.evaluate(&vec![true, false], true), false);
-        assert_eq!(two_var_node.evaluate(&vec![false, true], true), false);
-        assert_eq!(two_var_node.evaluate(&vec![false, false], true), true);
+        assert!(!two_var_node.evaluate(&vec![true, true], true));
+        assert!(!two_var_node.evaluate(&vec![true, false], true));
+        assert!(!two_var_node.evaluate(&vec![false, true], true));
+        assert!(two_var_node.evaluate(&vec![false, false], true));
 
         // Switch to OR
         // None OR None
         two_var_node.generate_next();
-        assert_eq!(two_var_node.evaluate(&vec![true, true], true), true);
-        assert_eq!(two_var_node.evaluate(&vec![true, false], true), true);
-        assert_eq!(two_var_node.evaluate(&vec![false, true], true), true);
-        assert_eq!(two_var_node.evaluate(&vec![false, false], true), false);
+        assert!(two_var_node.evaluate(&vec![true, true], true));
+        assert!(two_var_node.evaluate(&vec![true, false], true));
+        assert!(two_var_node.evaluate(&vec![false, true], true));
+        assert!(!two_var_node.evaluate(&vec![false, false], true));
 
         // Not OR None
         two_var_node.generate_next();
-        assert_eq!(two_var_node.evaluate(&vec![true, true], true), true);
-        assert_eq!(two_var_node.evaluate(&vec![true, false], true), false);
-        assert_eq!(two_var_node.evaluate(&vec![false, true], true), true);
-        assert_eq!(two_var_node.evaluate(&vec![false, false], true), true);
+        assert!(two_var_node.evaluate(&vec![true, true], true));
+        assert!(!two_var_node.evaluate(&vec![true, false], true));
+        assert!(two_var_node.evaluate(&vec![false, true], true));
+        assert!(two_var_node.evaluate(&vec![false, false], true));
 
         // None OR Not
         two_var_node.generate_next();
-        assert_eq!(two_var_node.evaluate(&vec![true, true], true), true);
-        assert_eq!(two_var_node.evaluate(&vec![true, false], true), true);
-        assert_eq!(two_var_node.evaluate(&vec![false, true], true), false);
-        assert_eq!(two_var_node.evaluate(&vec![false, false], true), true);
+        assert!(two_var_node.evaluate(&vec![true, true], true));
+        assert!(two_var_node.evaluate(&vec![true, false], true));
+        assert!(!two_var_node.evaluate(&vec![false, true], true));
+        assert!(two_var_node.evaluate(&vec![false, false], true));
 
 
         // Not OR Not
         two_var_node.generate_next();
-        assert_eq!(two_var_node.evaluate(&vec![true, true], true), false);
-        assert_eq!(two_var_node.evaluate(&vec![true, false], true), true);
-        assert_eq!(two_var_node.evaluate(&vec![false, true], true), true);
-        assert_eq!(two_var_node.evaluate(&vec![false, false], true), true);
+        assert!(!two_var_node.evaluate(&vec![true, true], true));
+        assert!(two_var_node.evaluate(&vec![true, false], true));
+        assert!(two_var_node.evaluate(&vec![false, true], true));
+        assert!(two_var_node.evaluate(&vec![false, false], true));
 
     }
 
@@ -549,29 +669,29 @@ mod test {
         test_leaf_node(&mut and_node_right_child, 1);
 
         // Being made Or here
-        assert_eq!(and_node.has_next(), true);
+        assert!(and_node.has_next());
 
         and_node.generate_next();
         assert_eq!(and_node.operation, Op::And);
-        assert_eq!(and_node.has_next(), true);
+        assert!(and_node.has_next());
         assert_eq!(and_node.left_child.clone().unwrap().operation, Op::Not);
         assert_eq!(and_node.right_child.clone().unwrap().operation, Op::None);
 
         and_node.generate_next();
         assert_eq!(and_node.operation, Op::And);
-        assert_eq!(and_node.has_next(), true);
+        assert!(and_node.has_next());
         assert_eq!(and_node.left_child.clone().unwrap().operation, Op::None);
         assert_eq!(and_node.right_child.clone().unwrap().operation, Op::Not);
 
         and_node.generate_next();
         assert_eq!(and_node.operation, Op::And);
-        assert_eq!(and_node.has_next(), true);
+        assert!(and_node.has_next());
         assert_eq!(and_node.left_child.clone().unwrap().operation, Op::Not);
         assert_eq!(and_node.right_child.clone().unwrap().operation, Op::Not);
 
         and_node.generate_next();
         assert_eq!(and_node.operation, Op::Or);
-        assert_eq!(and_node.has_next(), true);
+        assert!(and_node.has_next());
         assert_eq!(and_node.left_child.clone().unwrap().operation, Op::None);
         assert_eq!(and_node.right_child.clone().unwrap().operation, Op::None);
         
@@ -596,11 +716,64 @@ mod test {
         if node.has_next() {
             assert_eq!(node.operation, Op::None);
             node.generate_next();
-            assert_eq!(node.has_next(), false);
+            assert!(!node.has_next());
             assert_eq!(node.operation, Op::Not);
         }
         else {
             assert_eq!(node.operation, Op::Not);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_to_expression() {
+        // The expression reads the inputs the permuted tree does
+        let mut node = OpNode::new(0, 3);
+        let mut counter = 0;
+        loop {
+            loop {
+                let expression = node.to_expression();
+                for config in 0..16 {
+                    let values: Vec<bool> = (0..4).map(|i| config >> i & 1 == 1).collect();
+                    assert_eq!(expression.evaluate(&values), node.evaluate(&values, true));
+                }
+                if !node.has_next_eval_permutation() {
+                    break;
+                }
+                node.generate_next_eval_permutation();
+            }
+
+            if !node.has_next() || counter > 200 {
+                break;
+            }
+            node.generate_next();
+            counter += 1;
+        }
+
+        let mut node = OpNode::from_state(0, 2, "& 0 ! | 1 v v").unwrap();
+        node.eval_permutation = SetPermuter::from_state("2,0,1").unwrap();
+        assert_eq!(node.to_string(), "( !0 & ( 1 | 2 ) )");
+        assert_eq!(node.to_expression().to_string(), "( !2 & ( 0 | 1 ) )");
+    }
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut node = OpNode::new(0, 4);
+        let mut counter = 0;
+        loop {
+            let restored = OpNode::from_state(0, 4, &node.get_state()).unwrap();
+            assert_eq!(restored, node);
+
+            if !node.has_next() || counter > 2000 {
+                break;
+            }
+            node.generate_next();
+            counter += 1;
+        }
+
+        assert_eq!(OpNode::new(0, 2).get_state(), "& 0 v & 1 v v");
+        assert!(OpNode::from_state(0, 2, "& 0 v & 1 v").is_err());
+        assert!(OpNode::from_state(0, 2, "& 0 v & 1 v v v").is_err());
+        assert!(OpNode::from_state(0, 2, "& 2 v & 1 v v").is_err());
+        assert!(OpNode::from_state(0, 2, "^ 0 v & 1 v v").is_err());
+    }
+}
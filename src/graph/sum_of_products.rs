@@ -0,0 +1,267 @@
+/*
+* Minimal sums of products
+*
+* A truth table written as an Or of Ands of inputs or their negations, with
+* as few products as possible, then as few inputs in them. Enumerated
+* conditions read every input of the neighbourhood, even when the function
+* only depends on a few of them: ( 0 & ( 1 | !1 ) ) is just 0.
+*
+* It's found by Quine-McCluskey: products true on configurations differing in
+* a single input are merged, without that input, until none merge. The
+* products left are the prime implicants, and the fewest of them covering
+* every true configuration are searched for, branching on the configuration
+* covered by the fewest primes. The search is exponential in the worst case,
+* so after MAX_COVER_STEPS branches the best cover found so far is kept,
+* which is still a sum of primes equal to the table but may not be minimal.
+* Conditions of the enumeration, where each input is read once, are minimised
+* at once.
+*/
+
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::graph::truth_table::TruthTable;
+
+// Product of the inputs of mask, input i being negated when bit i of value
+// isn't set. The bits of value outside of mask are unset
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Implicant {
+    pub mask: usize,
+    pub value: usize
+}
+
+impl Implicant {
+    // Whether the product is true in the configuration
+    pub fn covers(&self, config: usize) -> bool {
+        config & self.mask == self.value
+    }
+
+    pub fn literals(&self) -> usize {
+        self.mask.count_ones() as usize
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SumOfProducts {
+    inputs: usize,
+    terms: Vec<Implicant>,
+    // Whether the search for the fewest products finished, see MAX_COVER_STEPS
+    minimal: bool
+}
+
+impl SumOfProducts {
+    pub fn minimise(table: &TruthTable) -> Self {
+        let minterms: Vec<usize> = (0..table.len()).filter(|config| table.get(*config)).collect();
+        let primes = prime_implicants(table.inputs(), &minterms);
+
+        let mut cover = Cover {
+            primes: &primes,
+            covering: minterms.iter().map(|minterm| (0..primes.len()).filter(|i| primes[*i].covers(*minterm)).collect()).collect(),
+            best: None,
+            steps: 0
+        };
+        cover.search((0..minterms.len()).collect(), &mut vec![]);
+        let minimal = cover.steps <= MAX_COVER_STEPS;
+        let mut terms: Vec<Implicant> = cover.best.unwrap_or_default().into_iter().map(|i| primes[i]).collect();
+        // Most general products first, then by their inputs in order, unnegated first
+        let inputs = table.inputs();
+        terms.sort_by_key(|term| (term.literals(), (0..inputs).filter(|input| term.mask >> input & 1 == 1)
+            .map(|input| (input, term.value >> input & 1 == 0))
+            .collect::<Vec<(usize, bool)>>()));
+
+        SumOfProducts {
+            inputs: table.inputs(),
+            terms,
+            minimal
+        }
+    }
+
+    pub fn terms(&self) -> &[Implicant] {
+        &self.terms
+    }
+
+    pub fn is_minimal(&self) -> bool {
+        self.minimal
+    }
+
+    // Inputs the function depends on, in order
+    pub fn inputs_read(&self) -> Vec<usize> {
+        let mask = self.terms.iter().fold(0, |mask, term| mask | term.mask);
+        (0..self.inputs).filter(|input| mask >> input & 1 == 1).collect()
+    }
+
+    pub fn evaluate(&self, values: &[bool]) -> bool {
+        let config = values.iter().enumerate().fold(0, |config, (i, value)| config | (*value as usize) << i);
+        self.terms.iter().any(|term| term.covers(config))
+    }
+
+    // Products separated by " | ", inputs by " & ", which Expression::parse reads back
+    // unless the function is constant, "false" or "true"
+    pub fn to_named_string(&self, name: &dyn Fn(usize) -> String) -> String {
+        if self.terms.is_empty() {
+            return "false".to_string();
+        }
+        if self.terms[0].mask == 0 {
+            return "true".to_string();
+        }
+
+        let product = |term: &Implicant| (0..self.inputs).filter(|input| term.mask >> input & 1 == 1)
+            .map(|input| if term.value >> input & 1 == 1 { name(input) } else { format!("!{}", name(input)) })
+            .collect::<Vec<String>>()
+            .join(" & ");
+        self.terms.iter().map(product).collect::<Vec<String>>().join(" | ")
+    }
+}
+
+impl fmt::Display for SumOfProducts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_named_string(&|i| i.to_string()))
+    }
+}
+
+// Products of the minterms merged until none merge
+fn prime_implicants(inputs: usize, minterms: &[usize]) -> Vec<Implicant> {
+    let full = (1 << inputs) - 1;
+    let mut current: HashSet<Implicant> = minterms.iter().map(|minterm| Implicant { mask: full, value: *minterm }).collect();
+    let mut primes = vec![];
+
+    while !current.is_empty() {
+        let mut next = HashSet::new();
+        let mut merged = HashSet::new();
+        for implicant in current.iter() {
+            for input in (0..inputs).filter(|input| implicant.mask >> input & 1 == 1) {
+                let other = Implicant { mask: implicant.mask, value: implicant.value ^ (1 << input) };
+                if current.contains(&other) {
+                    merged.insert(*implicant);
+                    next.insert(Implicant { mask: implicant.mask & !(1 << input), value: implicant.value & !(1 << input) });
+                }
+            }
+        }
+        primes.extend(current.difference(&merged).copied());
+        current = next;
+    }
+
+    primes.sort_unstable();
+    primes
+}
+
+// Branches of the search for the fewest primes before settling for the best cover found
+const MAX_COVER_STEPS: usize = 100_000;
+
+// Search for the fewest primes covering the minterms, then the fewest literals
+struct Cover<'a> {
+    primes: &'a [Implicant],
+    // Primes covering each minterm
+    covering: Vec<Vec<usize>>,
+    best: Option<Vec<usize>>,
+    steps: usize
+}
+
+impl Cover<'_> {
+    fn literals(&self, terms: &[usize]) -> usize {
+        terms.iter().map(|i| self.primes[*i].literals()).sum()
+    }
+
+    // uncovered are indices of minterms
+    fn search(&mut self, uncovered: Vec<usize>, chosen: &mut Vec<usize>) {
+        if uncovered.is_empty() {
+            let better = match &self.best {
+                Some(best) => (chosen.len(), self.literals(chosen)) < (best.len(), self.literals(best)),
+                None => true
+            };
+            if better {
+                self.best = Some(chosen.clone());
+            }
+            return;
+        }
+        self.steps += 1;
+
+        // Minterms no prime covers two of each need their own prime
+        let mut used = vec![false; self.primes.len()];
+        let mut needed = 0;
+        for minterm in uncovered.iter() {
+            if self.covering[*minterm].iter().all(|i| !used[*i]) {
+                needed += 1;
+                for i in self.covering[*minterm].iter() {
+                    used[*i] = true;
+                }
+            }
+        }
+        if self.best.as_ref().is_some_and(|best| chosen.len() + needed > best.len() || self.steps > MAX_COVER_STEPS) {
+            return;
+        }
+
+        // Some prime has to cover the minterm with the fewest of them, those covering the
+        // most minterms are tried first for a good bound early
+        let minterm = *uncovered.iter().min_by_key(|minterm| self.covering[**minterm].len()).unwrap();
+        let mut candidates = self.covering[minterm].clone();
+        let count = |i: &usize| uncovered.iter().filter(|minterm| self.covering[**minterm].contains(i)).count();
+        candidates.sort_by_cached_key(|i| std::cmp::Reverse(count(i)));
+
+        for i in candidates {
+            chosen.push(i);
+            let uncovered = uncovered.iter().copied().filter(|minterm| !self.covering[*minterm].contains(&i)).collect();
+            self.search(uncovered, chosen);
+            chosen.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::graph::expression::Expression;
+    use crate::neighbourhood::Neighbourhood;
+
+    fn minimise(inputs: usize, f: impl Fn(&Vec<bool>) -> bool) -> SumOfProducts {
+        SumOfProducts::minimise(&TruthTable::from_fn(inputs, f))
+    }
+
+    #[test]
+    fn test_minimise() {
+        // Inputs the function doesn't depend on are left out
+        let parse = |text: &str| {
+            let expression = Expression::parse(text, 1, Neighbourhood::Moore(2)).unwrap();
+            SumOfProducts::minimise(&TruthTable::from_fn(5, |v| expression.evaluate(v)))
+        };
+        let sop = parse("( 1 & !2 & ( 0 | !3 ) ) | ( 1 & !2 & 3 )");
+        assert_eq!(sop.to_string(), "1 & !2");
+        assert_eq!(sop.inputs_read(), vec![1, 2]);
+        assert_eq!(parse("0 & ( 1 | !1 )").to_string(), "0");
+
+        // Majority needs every pair
+        let majority = minimise(3, |v| v.iter().filter(|el| **el).count() >= 2);
+        assert_eq!(majority.to_string(), "0 & 1 | 0 & 2 | 1 & 2");
+        assert_eq!(minimise(2, |v| v[0] != v[1]).to_string(), "0 & !1 | !0 & 1");
+
+        // Cyclic: every prime covers 2 of the 6 minterms, and 3 of the 6 primes are needed
+        let cyclic = minimise(3, |v| ![3, 4].contains(&(v[0] as usize | (v[1] as usize) << 1 | (v[2] as usize) << 2)));
+        assert_eq!(cyclic.terms().len(), 3);
+        assert!(cyclic.terms().iter().all(|term| term.literals() == 2));
+
+        assert_eq!(minimise(2, |_| false).to_string(), "false");
+        assert_eq!(minimise(2, |_| true).to_string(), "true");
+        assert_eq!(minimise(2, |_| true).inputs_read(), Vec::<usize>::new());
+        assert_eq!(majority.to_named_string(&|i| ["C", "N", "S"][i].to_string()), "C & N | C & S | N & S");
+    }
+
+    #[test]
+    fn test_equivalence() {
+        // Every function of 3 inputs, with primes for products
+        for number in 0..256_usize {
+            let table = TruthTable::from_fn(3, |v| number >> (v[0] as usize | (v[1] as usize) << 1 | (v[2] as usize) << 2) & 1 == 1);
+            let sop = SumOfProducts::minimise(&table);
+            for config in 0..table.len() {
+                assert_eq!(sop.evaluate(&table.values(config)), table.get(config), "{}", number);
+            }
+            for term in sop.terms() {
+                for input in (0..3).filter(|input| term.mask >> input & 1 == 1) {
+                    // Without any of its inputs, the product is true somewhere the function isn't
+                    let wider = Implicant { mask: term.mask & !(1 << input), value: term.value & !(1 << input) };
+                    assert!((0..table.len()).any(|config| wider.covers(config) && !table.get(config)), "{}", number);
+                }
+            }
+            assert_eq!(sop.inputs_read(), (0..3).filter(|input| table.depends_on(*input)).collect::<Vec<usize>>());
+        }
+    }
+}
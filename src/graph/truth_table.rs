@@ -0,0 +1,454 @@
+/*
+* Truth table of a boolean function
+*
+* A bitset with one bit per configuration of the inputs, configuration c setting
+* input i when bit i of c is set. Two conditions computing the same function
+* have the same table, whatever their OpNode trees, so it's the canonical form
+* used to compare rules and to number them.
+*
+* Converting back to an OpNode is only possible for functions an OpNode can
+* express: every input is read exactly once, so the function has to depend on
+* every input and split into an And or an Or of functions of disjoint inputs,
+* down to single inputs.
+*/
+
+use std::fmt;
+
+use crate::graph::expression::Expression;
+use crate::graph::op_node::OpNode;
+use crate::permutation::set_permuter::SetPermuter;
+
+// Beyond this, tables don't fit in memory
+pub const MAX_INPUTS: usize = 24;
+
+// Word of the table of input i < 6, which alternates within each word
+const INPUT_WORDS: [u64; 6] = [
+    0xAAAA_AAAA_AAAA_AAAA,
+    0xCCCC_CCCC_CCCC_CCCC,
+    0xF0F0_F0F0_F0F0_F0F0,
+    0xFF00_FF00_FF00_FF00,
+    0xFFFF_0000_FFFF_0000,
+    0xFFFF_FFFF_0000_0000
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TruthTable {
+    inputs: usize,
+    bits: Vec<u64>
+}
+
+impl TruthTable {
+    // Always false
+    pub fn new(inputs: usize) -> Self {
+        if inputs > MAX_INPUTS {
+            panic!("Truth table of {} inputs is larger than {} inputs", inputs, MAX_INPUTS);
+        }
+
+        TruthTable {
+            inputs,
+            bits: vec![0; (1_usize << inputs).div_ceil(64)]
+        }
+    }
+
+    pub fn from_fn(inputs: usize, f: impl Fn(&Vec<bool>) -> bool) -> Self {
+        let mut table = TruthTable::new(inputs);
+        for config in 0..table.len() {
+            if f(&table.values(config)) {
+                table.set(config, true);
+            }
+        }
+        table
+    }
+
+    pub fn from_op_node(node: &OpNode) -> Self {
+        TruthTable::from_expression(&node.to_expression(), node.len())
+    }
+
+    // Evaluated on every configuration at once, a word of configurations at a time
+    pub fn from_expression(expression: &Expression, inputs: usize) -> Self {
+        match expression {
+            Expression::Input(i) => TruthTable::input(inputs, *i),
+            Expression::Not(expression) => TruthTable::from_expression(expression, inputs).negate(),
+            Expression::And(left, right) => TruthTable::from_expression(left, inputs).and(&TruthTable::from_expression(right, inputs)),
+            Expression::Or(left, right) => TruthTable::from_expression(left, inputs).or(&TruthTable::from_expression(right, inputs))
+        }
+    }
+
+    // True where input i is
+    pub fn input(inputs: usize, i: usize) -> Self {
+        let mut table = TruthTable::new(inputs);
+        for (word, bits) in table.bits.iter_mut().enumerate() {
+            *bits = match INPUT_WORDS.get(i) {
+                Some(bits) => *bits,
+                None if word >> (i - 6) & 1 == 1 => u64::MAX,
+                None => 0
+            };
+        }
+        table.clear_unused();
+        table
+    }
+
+    pub fn constant(inputs: usize, value: bool) -> Self {
+        let table = TruthTable::new(inputs);
+        if value { table.negate() } else { table }
+    }
+
+    // Bits past the last configuration, in tables of less than 6 inputs, stay unset
+    fn clear_unused(&mut self) {
+        if self.len() < 64 {
+            self.bits[0] &= (1 << self.len()) - 1;
+        }
+    }
+
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    // Number of configurations
+    pub fn len(&self) -> usize {
+        1 << self.inputs
+    }
+
+    pub fn get(&self, config: usize) -> bool {
+        self.bits[config / 64] >> (config % 64) & 1 == 1
+    }
+
+    pub fn set(&mut self, config: usize, value: bool) {
+        if value {
+            self.bits[config / 64] |= 1 << (config % 64);
+        }
+        else {
+            self.bits[config / 64] &= !(1 << (config % 64));
+        }
+    }
+
+    // Input values of the configuration
+    pub fn values(&self, config: usize) -> Vec<bool> {
+        (0..self.inputs).map(|i| config >> i & 1 == 1).collect()
+    }
+
+    pub fn evaluate(&self, values: &[bool]) -> bool {
+        let config = values.iter().enumerate().fold(0, |config, (i, value)| config | (*value as usize) << i);
+        self.get(config)
+    }
+
+    // Configurations where the function is true
+    pub fn count_ones(&self) -> usize {
+        self.bits.iter().map(|bits| bits.count_ones() as usize).sum()
+    }
+
+    pub fn negate(&self) -> Self {
+        let mut table = TruthTable {
+            inputs: self.inputs,
+            bits: self.bits.iter().map(|bits| !bits).collect()
+        };
+        table.clear_unused();
+        table
+    }
+
+    pub fn and(&self, other: &TruthTable) -> Self {
+        TruthTable {
+            inputs: self.inputs,
+            bits: self.bits.iter().zip(other.bits.iter()).map(|(bits, other)| bits & other).collect()
+        }
+    }
+
+    pub fn or(&self, other: &TruthTable) -> Self {
+        TruthTable {
+            inputs: self.inputs,
+            bits: self.bits.iter().zip(other.bits.iter()).map(|(bits, other)| bits | other).collect()
+        }
+    }
+
+    // if_true where this table is true, if_false elsewhere
+    pub fn select(&self, if_true: &TruthTable, if_false: &TruthTable) -> Self {
+        self.and(if_true).or(&self.negate().and(if_false))
+    }
+
+    // The table 8 configurations to a byte, the first configuration in the lowest bit
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = self.bits.iter().flat_map(|bits| bits.to_le_bytes()).collect();
+        bytes.truncate(self.len().div_ceil(8));
+        bytes
+    }
+
+    // Same function, with input i of the result being input order[i] of this table
+    pub fn reorder(&self, order: &[usize]) -> Self {
+        TruthTable::from_fn(self.inputs, |values| {
+            let mut original = vec![false; self.inputs];
+            for (i, input) in order.iter().enumerate() {
+                original[*input] = values[i];
+            }
+            self.evaluate(&original)
+        })
+    }
+
+    // Whether the value of the input changes the value of the function
+    pub fn depends_on(&self, input: usize) -> bool {
+        (0..self.len()).any(|config| self.get(config) != self.get(config ^ (1 << input)))
+    }
+
+    // Number whose binary digits are the table, the first configuration
+    // being the lowest digit. Written in decimal as it can be far larger than any integer
+    pub fn to_number(&self) -> String {
+        let mut digits: Vec<u64> = self.bits.clone();
+        let mut decimal = vec![];
+
+        // Repeated division of the whole table by 10
+        while digits.iter().any(|el| *el != 0) {
+            let mut remainder: u128 = 0;
+            for el in digits.iter_mut().rev() {
+                let value = (remainder << 64) | *el as u128;
+                *el = (value / 10) as u64;
+                remainder = value % 10;
+            }
+            decimal.push(char::from(b'0' + remainder as u8));
+        }
+
+        if decimal.is_empty() {
+            return "0".to_string();
+        }
+        decimal.iter().rev().collect()
+    }
+
+    pub fn from_number(inputs: usize, number: &str) -> Result<Self, String> {
+        let mut table = TruthTable::new(inputs);
+        let mut overflow = false;
+
+        for c in number.trim().chars() {
+            let digit = c.to_digit(10).ok_or(format!("Invalid rule number {}", number))? as u128;
+
+            // table = table * 10 + digit
+            let mut carry = digit;
+            for el in table.bits.iter_mut() {
+                let value = *el as u128 * 10 + carry;
+                *el = value as u64;
+                carry = value >> 64;
+            }
+            overflow |= carry != 0;
+        }
+
+        // Bits past the last configuration
+        if table.len() < 64 {
+            overflow |= table.bits[0] >> table.len() != 0;
+        }
+
+        if overflow {
+            return Err(format!("Rule number {} is larger than a table of {} inputs", number, inputs));
+        }
+        Ok(table)
+    }
+
+    // An OpNode computing the function, if there's one. It isn't a position
+    // of the enumeration, but evaluates like any other
+    pub fn to_op_node(&self) -> Option<OpNode> {
+        if self.inputs == 0 {
+            return None;
+        }
+
+        let all_inputs: Vec<usize> = (0..self.inputs).collect();
+        let mut tokens = vec![];
+        let mut order = vec![];
+        if !self.decompose(&all_inputs, &mut tokens, &mut order) {
+            return None;
+        }
+
+        let mut node = OpNode::from_state(0, self.inputs - 1, &tokens.join(" ")).ok()?;
+        node.eval_permutation = SetPermuter::new(vec![order]);
+        Some(node)
+    }
+
+    // Writes the tree of the function restricted to inputs, in the pre-order of
+    // OpNode::get_state, and the inputs of its leaves in order. Leaf positions
+    // start at the length of order
+    fn decompose(&self, inputs: &[usize], tokens: &mut Vec<String>, order: &mut Vec<usize>) -> bool {
+        // Every input is read once, so the function has to depend on each of them
+        if inputs.iter().any(|input| !self.depends_on(*input)) {
+            return false;
+        }
+
+        if inputs.len() == 1 {
+            // Depending on its only input, the function is the input or its negation
+            let is_input = (0..self.len()).all(|config| self.get(config) == (config >> inputs[0] & 1 == 1));
+            tokens.push(if is_input { "v" } else { "!" }.to_string());
+            order.push(inputs[0]);
+            return true;
+        }
+
+        // Partitions of the inputs, the first one always on the left
+        for partition in 0..1_usize << (inputs.len() - 1) {
+            let mut left = vec![inputs[0]];
+            let mut right = vec![];
+            for (i, input) in inputs.iter().enumerate().skip(1) {
+                if partition >> (i - 1) & 1 == 1 {
+                    left.push(*input);
+                }
+                else {
+                    right.push(*input);
+                }
+            }
+            if right.is_empty() {
+                continue;
+            }
+
+            for (op, table) in [("&", self.clone()), ("|", self.negate())] {
+                // f = g(left) & h(right) when f is true exactly where both its
+                // projections on the left and right inputs are. Or is the same on !f
+                if let Some((left_table, right_table)) = table.split_and(&left, &right) {
+                    let (left_table, right_table) = if op == "|" {
+                        (left_table.negate(), right_table.negate())
+                    }
+                    else {
+                        (left_table, right_table)
+                    };
+
+                    let start = order.len();
+                    tokens.push(op.to_string());
+                    tokens.push((start + left.len() - 1).to_string());
+                    return left_table.decompose(&left, tokens, order)
+                        && right_table.decompose(&right, tokens, order);
+                }
+            }
+        }
+
+        false
+    }
+
+    // g and h, over the same inputs as this table, with self = g & h,
+    // g only depending on left and h on right
+    fn split_and(&self, left: &[usize], right: &[usize]) -> Option<(TruthTable, TruthTable)> {
+        let mask = |inputs: &[usize]| inputs.iter().fold(0, |mask, input| mask | 1 << input);
+        let (left_mask, right_mask) = (mask(left), mask(right));
+
+        // Projections: whether some configuration agreeing on the inputs is true
+        let mut g = TruthTable::new(self.inputs);
+        let mut h = TruthTable::new(self.inputs);
+        for config in (0..self.len()).filter(|config| self.get(*config)) {
+            g.set(config & left_mask, true);
+            h.set(config & right_mask, true);
+        }
+        let g = TruthTable::from_fn(self.inputs, |values| g.get(config_of(values) & left_mask));
+        let h = TruthTable::from_fn(self.inputs, |values| h.get(config_of(values) & right_mask));
+
+        if (0..self.len()).all(|config| self.get(config) == (g.get(config) && h.get(config))) {
+            return Some((g, h));
+        }
+        None
+    }
+}
+
+fn config_of(values: &[bool]) -> usize {
+    values.iter().enumerate().fold(0, |config, (i, value)| config | (*value as usize) << i)
+}
+
+impl fmt::Display for TruthTable {
+    // Output of every configuration, the first configuration first
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for config in 0..self.len() {
+            write!(f, "{}", self.get(config) as u8)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_op_node_round_trip() {
+        // Every candidate of 3 inputs is an And/Or of all its inputs, so converts back
+        let mut node = OpNode::new(0, 2);
+        loop {
+            let table = TruthTable::from_op_node(&node);
+            let converted = table.to_op_node().unwrap();
+            assert_eq!(TruthTable::from_op_node(&converted), table);
+
+            if node.has_next_eval_permutation() {
+                node.generate_next_eval_permutation();
+            }
+            else if node.has_next() {
+                node.generate_next();
+            }
+            else {
+                break;
+            }
+        }
+
+        let node = OpNode::new(0, 5);
+        let converted = TruthTable::from_op_node(&node).to_op_node().unwrap();
+        assert_eq!(TruthTable::from_op_node(&converted), TruthTable::from_op_node(&node));
+    }
+
+    #[test]
+    fn test_from_expression() {
+        // Evaluated a word at a time, within a word and across words
+        for inputs in [3, 7] {
+            let mut node = OpNode::new(0, inputs - 1);
+            for _ in 0..500 {
+                let slow = TruthTable::from_fn(inputs, |values| node.evaluate(values, true));
+                assert_eq!(TruthTable::from_op_node(&node), slow);
+                assert_eq!(TruthTable::from_op_node(&node).negate(), TruthTable::from_fn(inputs, |values| !slow.evaluate(values)));
+                if !node.has_next() {
+                    break;
+                }
+                node.generate_next();
+            }
+        }
+
+        assert_eq!(TruthTable::constant(3, true).count_ones(), 8);
+        assert_eq!(TruthTable::input(7, 6).count_ones(), 64);
+        assert_eq!(TruthTable::input(3, 1).to_bytes(), vec![0b1100_1100]);
+        assert_eq!(TruthTable::input(2, 0).select(&TruthTable::constant(2, false), &TruthTable::input(2, 1)).to_string(), "0010");
+    }
+
+    #[test]
+    fn test_not_expressible() {
+        // Exclusive or reads its inputs twice, and constants or functions
+        // ignoring an input leave a leaf without a use
+        assert_eq!(TruthTable::from_fn(2, |v| v[0] != v[1]).to_op_node(), None);
+        assert_eq!(TruthTable::from_fn(3, |v| v[0] && v[1]).to_op_node(), None);
+        assert_eq!(TruthTable::new(2).to_op_node(), None);
+
+        let majority = TruthTable::from_fn(3, |v| v.iter().filter(|el| **el).count() >= 2);
+        assert_eq!(majority.to_op_node(), None);
+
+        // (x0 | !x2) & (x1 | x3) has its inputs interleaved
+        let table = TruthTable::from_fn(4, |v| (v[0] || !v[2]) && (v[1] || v[3]));
+        let node = table.to_op_node().unwrap();
+        assert_eq!(TruthTable::from_op_node(&node), table);
+    }
+
+    #[test]
+    fn test_number() {
+        // Elementary rule 30 over configurations 0 to 7
+        let table = TruthTable::from_fn(3, |v| {
+            let config = v[0] as u8 | (v[1] as u8) << 1 | (v[2] as u8) << 2;
+            30 >> config & 1 == 1
+        });
+        assert_eq!(table.to_number(), "30");
+        assert_eq!(table.count_ones(), 4);
+        assert_eq!(table.to_string(), "01111000");
+        assert_eq!(TruthTable::from_number(3, "30").unwrap(), table);
+        assert!(TruthTable::from_number(3, "256").is_err());
+        assert!(TruthTable::from_number(3, "3x").is_err());
+
+        // Larger than 128 bits
+        let mut table = TruthTable::new(9);
+        table.set(511, true);
+        table.set(0, true);
+        let number = table.to_number();
+        assert_eq!(number, "6703903964971298549787012499102923063739682910296196688861780721860882015036773488400937149083451713845015929093243025426876941405973284973216824503042049");
+        assert_eq!(TruthTable::from_number(9, &number).unwrap(), table);
+        assert_eq!(TruthTable::new(9).to_number(), "0");
+    }
+
+    #[test]
+    fn test_reorder() {
+        let table = TruthTable::from_fn(3, |v| v[0] && !v[2]);
+        let reordered = table.reorder(&[2, 0, 1]);
+        assert!(reordered.evaluate(&[false, true, false]));
+        assert!(!reordered.evaluate(&[true, true, false]));
+        assert_eq!(reordered.reorder(&[1, 2, 0]), table);
+    }
+}
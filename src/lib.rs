@@ -0,0 +1,13 @@
+#![allow(clippy::len_without_is_empty)]
+
+pub mod cell;
+pub mod neighbourhood;
+pub mod topology;
+pub mod space;
+pub mod configuration;
+pub mod measure;
+pub mod rule;
+pub mod rule_set;
+pub mod state_explorer;
+pub mod graph;
+pub mod permutation;
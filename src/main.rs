@@ -1,13 +1,212 @@
-mod cell;
-mod space;
-mod rule;
-mod state_explorer;
-mod graph;
-mod permutation;
-use crate::state_explorer::StateExplorer;
-
-fn main() {
-    let explorer: StateExplorer = StateExplorer::new(1, 2, vec![1, 4, 9, 16, 25]);
-    //let explorer: StateExplorer = StateExplorer::new(2, 2, vec![1, 2, 3, 4, 5]);
-    explorer.explore();
-}   
+mod cli;
+
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use cellular_automata_state_search::measure;
+use cellular_automata_state_search::neighbourhood::InputNames;
+use cellular_automata_state_search::rule::{Family, Rule};
+use cellular_automata_state_search::state_explorer::checkpoint::Checkpoint;
+use cellular_automata_state_search::state_explorer::{RuleMatch, StateExplorer};
+
+use crate::cli::{Command, CountArgs, EmulateArgs, EnumerateArgs, SearchArgs};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let command = match cli::parse_args(&args) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("{}", err);
+            eprintln!("{}", cli::USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let result = match command {
+        Command::Search(args) => search(&args),
+        Command::Emulate(args) => emulate(&args),
+        Command::EnumerateRules(args) => enumerate_rules(&args),
+        Command::Count(args) => count(&args),
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(true)
+        }
+    };
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::from(2)
+        }
+    }
+}
+
+// Returns whether a rule was found
+fn search(args: &SearchArgs) -> Result<bool, String> {
+    let checkpoint = match &args.resume {
+        Some(resume) => Some(Checkpoint::load(Path::new(resume))?),
+        None => None
+    };
+
+    let mut explorer = match &checkpoint {
+        Some(checkpoint) => StateExplorer::from_checkpoint(checkpoint),
+        None => StateExplorer::new(args.min_dimensions, args.max_dimensions, args.sequence.clone())
+    };
+    if checkpoint.is_none() {
+        explorer.set_update_mode(args.update_mode);
+        explorer.set_measure(measure::from_name(&args.measure)?);
+        explorer.set_neighbourhood(args.neighbourhood);
+        explorer.set_topology(&args.topology);
+        explorer.set_family(args.family);
+        explorer.set_actions(args.actions);
+        explorer.set_states(args.states);
+        explorer.set_rule_set(args.rule_set_len, args.policy);
+    }
+    explorer.set_verbose(!args.quiet);
+    explorer.set_emulate_on_match(false);
+    explorer.set_threads(args.threads);
+    explorer.set_backend(args.backend);
+    explorer.set_dedup(args.dedup);
+    if let Some(path) = &args.checkpoint {
+        explorer.set_checkpoint(Path::new(path), Duration::from_secs(args.checkpoint_interval));
+    }
+
+    let (result, all, update_mode, topology) = match checkpoint {
+        Some(checkpoint) => {
+            let (all, update_mode, topology) = (checkpoint.exhaustive, checkpoint.update_mode, checkpoint.topology.clone());
+            (explorer.resume(checkpoint)?, all, update_mode, topology)
+        },
+        None => (explorer.explore_rules(args.all), args.all, args.update_mode, args.topology.clone())
+    };
+    let sequence = &result.expected_num_set_cells;
+
+    if all {
+        for dimension in result.dimensions.iter() {
+            println!("Dimension {}: {} of {} permutations generate the sequence", dimension.dimension, dimension.matches.len(), dimension.permutations_explored);
+        }
+    }
+
+    let found = result.matches();
+    if found.is_empty() {
+        println!("Found no rule that can generate sequence {:?}", sequence);
+    }
+
+    if let Some(output) = &args.output {
+        let mut file = OpenOptions::new().create(true).append(true).open(output)
+            .map_err(|err| format!("Could not open {}: {}", output, err))?;
+        for rule_match in found.iter().copied() {
+            write_match(&mut file, sequence, rule_match, args.input_names)
+                .map_err(|err| format!("Could not write {}: {}", output, err))?;
+        }
+    }
+
+    if let (Some(path), Some(rule_match)) = (&args.save_rule, found.first()) {
+        // Searches of single rules, checked when parsing
+        rule_match.rule_set.get_rules()[0].save(Path::new(path))
+            .map_err(|err| format!("Could not write {}: {}", path, err))?;
+    }
+
+    if args.emulate && !all {
+        if let Some(rule_match) = found.first() {
+            let rule_set = rule_match.rule_set.clone().with_input_names(args.input_names).with_minimal(true);
+            StateExplorer::emulate_rule_set(&rule_set, rule_match.dimension, update_mode, &topology, None);
+        }
+    }
+
+    Ok(!found.is_empty())
+}
+
+fn write_match(file: &mut File, sequence: &[usize], rule_match: &RuleMatch, input_names: InputNames) -> io::Result<()> {
+    writeln!(file, "Sequence: {:?}", sequence)?;
+    writeln!(file, "Dimension: {}", rule_match.dimension)?;
+    writeln!(file, "Index: {}", rule_match.index)?;
+    writeln!(file, "{}", rule_match.rule_set.clone().with_input_names(input_names).with_minimal(true))
+}
+
+fn emulate(args: &EmulateArgs) -> Result<bool, String> {
+    let rule = match (&args.rulestring, args.index, &args.rule_file, &args.condition) {
+        (Some(rulestring), _, _, _) => Rule::from_rulestring(args.dimension, args.neighbourhood, rulestring)?,
+        (None, Some(index), _, _) => rule_at_index(args, index)?,
+        (None, None, Some(path), _) => Rule::load(Path::new(path))?,
+        (None, None, None, Some(condition)) => Rule::from_expression(args.dimension, args.neighbourhood, condition, args.action, args.else_action)?
+            .with_states(args.states),
+        (None, None, None, None) => return Err("emulate requires one of --index, --rule, --rule-file and --condition".to_string())
+    };
+    let rule = rule.with_input_names(args.input_names).with_minimal(true);
+
+    StateExplorer::emulate_rule(&rule, rule.get_dimension(), args.update_mode, &args.topology, args.generations);
+    Ok(true)
+}
+
+fn rule_at_index(args: &EmulateArgs, index: usize) -> Result<Rule, String> {
+    if index == 0 {
+        return Err("--index starts at 1".to_string());
+    }
+
+    // Rules of a family are numbered from 0
+    if args.family != Family::Tree {
+        return Ok(Rule::from_number(args.dimension, args.neighbourhood, args.family, index as u128 - 1)?.with_states(args.states));
+    }
+
+    let mut rule = Rule::with_actions(args.dimension, args.neighbourhood, args.actions).with_states(args.states);
+    for _ in 1..index {
+        if !rule.has_next_candidate() {
+            return Err(format!("Dimension {} has fewer than {} rules", args.dimension, index));
+        }
+        rule.generate_next_candidate();
+    }
+
+    Ok(rule)
+}
+
+fn enumerate_rules(args: &EnumerateArgs) -> Result<bool, String> {
+    let mut rule = Rule::first(args.dimension, args.neighbourhood, args.family, args.actions).with_states(args.states)
+        .with_input_names(args.input_names).with_minimal(true);
+    let mut index = 1;
+    loop {
+        println!("#{}", index);
+        rule.print();
+
+        if !rule.has_next_candidate() || args.limit.is_some_and(|limit| index >= limit) {
+            break;
+        }
+        rule.generate_next_candidate();
+        index += 1;
+    }
+
+    Ok(true)
+}
+
+fn count(args: &CountArgs) -> Result<bool, String> {
+    for dim_len in args.min_dimensions..args.max_dimensions+1 {
+        // Every number of a family is one rule
+        if args.family != Family::Tree {
+            let rules = 1_u128 << args.family.rule_bits(dim_len, args.neighbourhood)?;
+            println!("Dimension {}: {} rules and {} permutations", dim_len, rules, rules);
+            continue;
+        }
+
+        let mut rule = Rule::with_actions(dim_len, args.neighbourhood, args.actions).with_states(args.states);
+        let mut rule_counter = 1;
+        let mut permutation_counter = 1;
+        while rule.has_next_candidate() {
+            if !rule.has_next_eval_permutation() {
+                rule_counter += 1;
+            }
+            rule.generate_next_candidate();
+            permutation_counter += 1;
+        }
+
+        println!("Dimension {}: {} rules and {} permutations", dim_len, rule_counter, permutation_counter);
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,283 @@
+/*
+* Measures of a generation
+*
+* The explorer compares one measure of each generation with the expected
+* sequence. Besides the population, measures describe how the set cells
+* grow (newly set cells) or their shape (bounding box, blocks, perimeter).
+* Cells of rules of more than 2 states are set in every state but Unset.
+*
+* Every measure has a name, which is how it's given on the command line
+* and saved in checkpoints: the measure, then its parameters separated by ":",
+* e.g. "population", "largest-component:moore" or "axis-population:1".
+*/
+
+use std::fmt::Debug;
+
+use crate::neighbourhood::Neighbourhood;
+use crate::space::Space;
+
+pub trait Measure: Debug + Send + Sync {
+    fn name(&self) -> String;
+
+    fn measure(&self, space: &Space) -> usize;
+
+    // Whether the generation matches the expected element of the sequence
+    fn matches(&self, space: &Space, expected: usize) -> bool {
+        self.measure(space) == expected
+    }
+
+    // Whether generations equal up to a translation have the same measure, so
+    // that once generations repeat, so do their measures
+    fn translation_invariant(&self) -> bool {
+        true
+    }
+
+    // Whether a generation only matches the element equal to its measure
+    fn single_valued(&self) -> bool {
+        true
+    }
+}
+
+// Number of set cells
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Population;
+
+impl Measure for Population {
+    fn name(&self) -> String {
+        "population".to_string()
+    }
+
+    fn measure(&self, space: &Space) -> usize {
+        space.count_set_cells()
+    }
+}
+
+// Number of cells set by the last generation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NewlySet;
+
+impl Measure for NewlySet {
+    fn name(&self) -> String {
+        "newly-set".to_string()
+    }
+
+    fn measure(&self, space: &Space) -> usize {
+        space.get_newly_set()
+    }
+}
+
+// Number of cells in the smallest box holding every set cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBoxVolume;
+
+impl Measure for BoundingBoxVolume {
+    fn name(&self) -> String {
+        "bounding-box".to_string()
+    }
+
+    fn measure(&self, space: &Space) -> usize {
+        let mut bounds: Vec<(i32, i32)> = vec![];
+        for cell in space.cells().filter(|cell| cell.get_value().is_set()) {
+            if bounds.is_empty() {
+                bounds = cell.get_coordinates().iter().map(|el| (*el, *el)).collect();
+            }
+            for (bound, el) in bounds.iter_mut().zip(cell.get_coordinates()) {
+                bound.0 = bound.0.min(*el);
+                bound.1 = bound.1.max(*el);
+            }
+        }
+
+        if bounds.is_empty() {
+            return 0;
+        }
+        bounds.iter().map(|(min, max)| (max - min + 1) as usize).product()
+    }
+}
+
+// Number of blocks of connected set cells
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Components(pub Neighbourhood);
+
+impl Measure for Components {
+    fn name(&self) -> String {
+        format!("components:{}", self.0.name())
+    }
+
+    fn measure(&self, space: &Space) -> usize {
+        space.component_sizes(self.0).len()
+    }
+}
+
+// Size of the largest block of connected set cells
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LargestComponent(pub Neighbourhood);
+
+impl Measure for LargestComponent {
+    fn name(&self) -> String {
+        format!("largest-component:{}", self.0.name())
+    }
+
+    fn measure(&self, space: &Space) -> usize {
+        space.component_sizes(self.0).first().copied().unwrap_or(0)
+    }
+}
+
+// Matches when any block of connected set cells has the expected size.
+// The measure itself is the size of the largest block
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockSizes(pub Neighbourhood);
+
+impl Measure for BlockSizes {
+    fn name(&self) -> String {
+        format!("blocks:{}", self.0.name())
+    }
+
+    fn measure(&self, space: &Space) -> usize {
+        LargestComponent(self.0).measure(space)
+    }
+
+    fn matches(&self, space: &Space, expected: usize) -> bool {
+        space.component_sizes(self.0).contains(&expected)
+    }
+
+    fn single_valued(&self) -> bool {
+        false
+    }
+}
+
+// Number of faces between a set cell and a cell which isn't set
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Perimeter;
+
+impl Measure for Perimeter {
+    fn name(&self) -> String {
+        "perimeter".to_string()
+    }
+
+    fn measure(&self, space: &Space) -> usize {
+        let mut perimeter = 0;
+        for cell in space.cells().filter(|cell| cell.get_value().is_set()) {
+            let offsets = Neighbourhood::VonNeumann(1).offsets(cell.len());
+            for offset in offsets.iter() {
+                let neighbour: Vec<i32> = cell.get_coordinates().iter().zip(offset).map(|(a, b)| a + b).collect();
+                if !space.search_cells(&neighbour).is_some_and(|c| c.get_value().is_set()) {
+                    perimeter += 1;
+                }
+            }
+        }
+        perimeter
+    }
+}
+
+// Number of set cells on an axis, i.e. with every other coordinate 0
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AxisPopulation(pub usize);
+
+impl Measure for AxisPopulation {
+    fn name(&self) -> String {
+        format!("axis-population:{}", self.0)
+    }
+
+    fn measure(&self, space: &Space) -> usize {
+        space.cells()
+            .filter(|cell| cell.get_value().is_set())
+            .filter(|cell| cell.get_coordinates().iter().enumerate().all(|(i, el)| i == self.0 || *el == 0))
+            .count()
+    }
+
+    fn translation_invariant(&self) -> bool {
+        false
+    }
+}
+
+// Measure with the given name, see the top of the file
+pub fn from_name(name: &str) -> Result<Box<dyn Measure>, String> {
+    let (measure, parameter) = match name.split_once(':') {
+        Some((measure, parameter)) => (measure, Some(parameter)),
+        None => (name, None)
+    };
+
+    let neighbourhood = || {
+        parameter.and_then(Neighbourhood::from_name)
+            .ok_or(format!("Measure {} requires a neighbourhood, moore or von-neumann", measure))
+    };
+
+    let measure: Box<dyn Measure> = match (measure, parameter) {
+        ("population", None) => Box::new(Population),
+        ("newly-set", None) => Box::new(NewlySet),
+        ("bounding-box", None) => Box::new(BoundingBoxVolume),
+        ("perimeter", None) => Box::new(Perimeter),
+        ("components", _) => Box::new(Components(neighbourhood()?)),
+        ("largest-component", _) => Box::new(LargestComponent(neighbourhood()?)),
+        ("blocks", _) => Box::new(BlockSizes(neighbourhood()?)),
+        ("axis-population", Some(axis)) => match axis.parse::<usize>() {
+            Ok(axis) => Box::new(AxisPopulation(axis)),
+            Err(_) => return Err(format!("Invalid axis {}", axis))
+        },
+        _ => return Err(format!("Unknown measure {}", name))
+    };
+
+    Ok(measure)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cell::Cell;
+
+    fn space_with_set_cells(dim_len: usize, coordinates: &[Vec<i32>]) -> Space {
+        let mut space = Space::new(dim_len);
+        for coordinate in coordinates {
+            let mut cell = Cell::new(dim_len);
+            cell.set_coordinates(coordinate.clone());
+            cell.set();
+            space.push_cell(&cell);
+        }
+        space
+    }
+
+    #[test]
+    fn test_measures() {
+        // A 2x2 square, a diagonal pair and a lone cell on the second axis
+        let space = space_with_set_cells(2, &[vec![0, 0], vec![0, 1], vec![1, 0], vec![1, 1], vec![3, 3], vec![4, 4], vec![0, -3]]);
+
+        assert_eq!(Population.measure(&space), 7);
+        assert_eq!(NewlySet.measure(&space), 7);
+        assert_eq!(BoundingBoxVolume.measure(&space), 5 * 8);
+        assert_eq!(Components(Neighbourhood::Moore(1)).measure(&space), 3);
+        assert_eq!(Components(Neighbourhood::VonNeumann(1)).measure(&space), 4);
+        assert_eq!(LargestComponent(Neighbourhood::Moore(1)).measure(&space), 4);
+        assert!(BlockSizes(Neighbourhood::Moore(1)).matches(&space, 2));
+        assert!(!BlockSizes(Neighbourhood::VonNeumann(1)).matches(&space, 2));
+        assert_eq!(Perimeter.measure(&space), 8 + 4 * 3);
+        assert_eq!(AxisPopulation(0).measure(&space), 2);
+        assert_eq!(AxisPopulation(1).measure(&space), 3);
+
+        let empty = Space::new(2);
+        assert_eq!(BoundingBoxVolume.measure(&empty), 0);
+        assert_eq!(LargestComponent(Neighbourhood::Moore(1)).measure(&empty), 0);
+    }
+
+    #[test]
+    fn test_from_name() {
+        let measures: Vec<Box<dyn Measure>> = vec![
+            Box::new(Population),
+            Box::new(NewlySet),
+            Box::new(BoundingBoxVolume),
+            Box::new(Components(Neighbourhood::Moore(1))),
+            Box::new(LargestComponent(Neighbourhood::VonNeumann(1))),
+            Box::new(BlockSizes(Neighbourhood::Moore(1))),
+            Box::new(Perimeter),
+            Box::new(AxisPopulation(2))
+        ];
+        for measure in measures {
+            assert_eq!(from_name(&measure.name()).unwrap().name(), measure.name());
+        }
+
+        assert!(from_name("components").is_err());
+        assert!(from_name("components:hexagonal").is_err());
+        assert!(from_name("population:moore").is_err());
+        assert!(from_name("axis-population:x").is_err());
+        assert!(from_name("volume").is_err());
+    }
+}
@@ -0,0 +1,299 @@
+/*
+* Neighbourhoods
+*
+* The cells a rule reads to update a cell: the cell itself followed by its
+* neighbours. The Moore neighbourhood of radius r has (2r + 1)^N cells, so rules
+* over it grow quickly with the dimension. The von Neumann neighbourhood of
+* radius 1 only has 2N + 1, which keeps the rules of higher dimensions small
+* enough to explore.
+*
+* Neighbours are ordered the way Cell explores nearby cells, the first coordinate
+* changing fastest and each coordinate going 0, +1, -1, +2, -2 .. +r, -r. So the
+* inputs of a rule are numbered the same whichever neighbourhood they come from,
+* and the first neighbours of a larger radius are those of a smaller one.
+*
+* Names are "moore" and "von-neumann" for radius 1, "moore:2" or
+* "von-neumann:3" for larger radii.
+*
+* Offsets are written as their coordinates with the sign of each, e.g. "(+1,-1)"
+* or "(+2)" in 1D, and "0" for the cell itself. In 2D the cells around it also
+* have compass names, as the space is printed: the first coordinate going down
+* the rows and the second along them, so N is (-1,0) and E is (0,+1).
+*
+* Printed rules write their inputs as InputNames says: "index" for their
+* index, "offset" for the offset of the cell they read, or "compass" for its
+* compass name when it has one.
+*/
+
+// Names of the offsets of radius 1 in 2D, the cell itself being C
+const COMPASS: [(&str, [i32; 2]); 9] = [
+    ("C", [0, 0]), ("N", [-1, 0]), ("NE", [-1, 1]), ("E", [0, 1]), ("SE", [1, 1]),
+    ("S", [1, 0]), ("SW", [1, -1]), ("W", [0, -1]), ("NW", [-1, -1])
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Neighbourhood {
+    // Every cell differing by at most the radius in each coordinate
+    Moore(usize),
+    // Cells whose coordinates differ by at most the radius in total
+    VonNeumann(usize)
+}
+
+impl Neighbourhood {
+    pub fn radius(&self) -> usize {
+        match self {
+            Neighbourhood::Moore(radius) | Neighbourhood::VonNeumann(radius) => *radius
+        }
+    }
+
+    // Same shape with another radius
+    pub fn with_radius(&self, radius: usize) -> Neighbourhood {
+        match self {
+            Neighbourhood::Moore(_) => Neighbourhood::Moore(radius),
+            Neighbourhood::VonNeumann(_) => Neighbourhood::VonNeumann(radius)
+        }
+    }
+
+    pub fn name(&self) -> String {
+        let shape = match self {
+            Neighbourhood::Moore(_) => "moore",
+            Neighbourhood::VonNeumann(_) => "von-neumann"
+        };
+
+        if self.radius() == 1 {
+            shape.to_string()
+        }
+        else {
+            format!("{}:{}", shape, self.radius())
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Neighbourhood> {
+        let (shape, radius) = match name.split_once(':') {
+            Some((shape, radius)) => (shape, radius.parse::<usize>().ok().filter(|radius| *radius > 0)?),
+            None => (name, 1)
+        };
+
+        match shape {
+            "moore" => Some(Neighbourhood::Moore(radius)),
+            "von-neumann" => Some(Neighbourhood::VonNeumann(radius)),
+            _ => None
+        }
+    }
+
+    // Number of cells a rule reads, the cell itself included
+    pub fn len(&self, dim_len: usize) -> usize {
+        match self {
+            Neighbourhood::Moore(radius) => (2 * radius + 1).pow(dim_len as u32),
+            Neighbourhood::VonNeumann(_) => self.offsets(dim_len).len() + 1
+        }
+    }
+
+    // Offsets of the neighbours, without the cell itself
+    pub fn offsets(&self, dim_len: usize) -> Vec<Vec<i32>> {
+        let radius = self.radius() as i32;
+        // Position of each coordinate in 0, +1, -1 .. +r, -r
+        let steps = 2 * radius + 1;
+        let step_offset = |step: i32| if step % 2 == 1 { (step + 1) / 2 } else { -step / 2 };
+
+        let mut offsets = vec![];
+        let mut steps_of_coordinates = vec![0; dim_len];
+        loop {
+            // Next offset, the first coordinate changing fastest
+            let mut i = 0;
+            while i < dim_len && steps_of_coordinates[i] == steps - 1 {
+                steps_of_coordinates[i] = 0;
+                i += 1;
+            }
+            if i == dim_len {
+                break;
+            }
+            steps_of_coordinates[i] += 1;
+
+            let offset: Vec<i32> = steps_of_coordinates.iter().map(|step| step_offset(*step)).collect();
+            let distance: i32 = offset.iter().map(|el| el.abs()).sum();
+            if matches!(self, Neighbourhood::Moore(_)) || distance <= radius {
+                offsets.push(offset);
+            }
+        }
+        offsets
+    }
+
+    // Offsets of the cells a rule reads, the cell itself first
+    pub fn inputs(&self, dim_len: usize) -> Vec<Vec<i32>> {
+        let mut inputs = vec![vec![0; dim_len]];
+        inputs.extend(self.offsets(dim_len));
+        inputs
+    }
+
+    // Input reading the cell at the offset, None when the neighbourhood doesn't read it
+    pub fn input_index(&self, dim_len: usize, offset: &[i32]) -> Option<usize> {
+        self.inputs(dim_len).iter().position(|input| input == offset)
+    }
+
+    // Name of the input, which Expression::parse reads back
+    pub fn input_name(&self, dim_len: usize, input: usize, names: InputNames) -> String {
+        let offset = &self.inputs(dim_len)[input];
+        match names {
+            InputNames::Index => input.to_string(),
+            InputNames::Compass if dim_len == 2 => compass_name(offset).map_or(offset_name(offset), |name| name.to_string()),
+            InputNames::Offset | InputNames::Compass => offset_name(offset)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputNames {
+    Index,
+    Offset,
+    // Offsets without a compass name, outside of radius 1 in 2D, are written as offsets
+    Compass
+}
+
+impl InputNames {
+    pub fn name(&self) -> &'static str {
+        match self {
+            InputNames::Index => "index",
+            InputNames::Offset => "offset",
+            InputNames::Compass => "compass"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<InputNames> {
+        match name {
+            "index" => Some(InputNames::Index),
+            "offset" => Some(InputNames::Offset),
+            "compass" => Some(InputNames::Compass),
+            _ => None
+        }
+    }
+}
+
+// Offset with the sign of each coordinate, see the top of the file
+pub fn offset_name(offset: &[i32]) -> String {
+    if offset.iter().all(|el| *el == 0) {
+        return "0".to_string();
+    }
+    let coordinates: Vec<String> = offset.iter().map(|el| if *el == 0 { "0".to_string() } else { format!("{:+}", el) }).collect();
+    format!("({})", coordinates.join(","))
+}
+
+// Compass name of an offset of radius 1 in 2D
+pub fn compass_name(offset: &[i32]) -> Option<&'static str> {
+    COMPASS.iter().find(|(_, compass)| compass.as_slice() == offset).map(|(name, _)| *name)
+}
+
+// Offset of a name of either kind, in the dimension. Coordinates may omit the + sign
+pub fn offset_from_name(dim_len: usize, name: &str) -> Option<Vec<i32>> {
+    let name = name.trim();
+    if name == "0" {
+        return Some(vec![0; dim_len]);
+    }
+    if let Some((_, offset)) = COMPASS.iter().find(|(compass, _)| *compass == name) {
+        return (dim_len == 2).then(|| offset.to_vec());
+    }
+
+    let coordinates = name.strip_prefix('(')?.strip_suffix(')')?;
+    let offset = coordinates.split(',').map(|el| el.trim().parse::<i32>().ok()).collect::<Option<Vec<i32>>>()?;
+    (offset.len() == dim_len).then_some(offset)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::cell::Cell;
+
+    #[test]
+    fn test_neighbourhood_offsets() {
+        assert_eq!(Neighbourhood::Moore(1).offsets(1), vec![vec![1], vec![-1]]);
+        assert_eq!(Neighbourhood::VonNeumann(1).offsets(1), vec![vec![1], vec![-1]]);
+        assert_eq!(Neighbourhood::Moore(1).offsets(2).len(), 8);
+        assert_eq!(Neighbourhood::VonNeumann(1).offsets(2), vec![vec![1, 0], vec![-1, 0], vec![0, 1], vec![0, -1]]);
+        assert_eq!(Neighbourhood::Moore(1).offsets(3).len(), 26);
+        assert_eq!(Neighbourhood::VonNeumann(1).offsets(3).len(), 6);
+
+        for dim_len in 1..5 {
+            for neighbourhood in [Neighbourhood::Moore(1), Neighbourhood::VonNeumann(1), Neighbourhood::Moore(2), Neighbourhood::VonNeumann(3)] {
+                let inputs = neighbourhood.inputs(dim_len);
+                assert_eq!(inputs.len(), neighbourhood.len(dim_len));
+                assert_eq!(inputs[0], vec![0; dim_len]);
+            }
+        }
+
+        // Radius 1 is the order Cell explores nearby cells in
+        for dim_len in 1..4 {
+            let mut cell = Cell::new(dim_len);
+            let mut offsets = vec![];
+            while cell.has_unexplored_nearby_cell() {
+                cell.generate_next_unexplored_nearby_cell();
+                offsets.push(cell.get_nearby_coordinate());
+            }
+            assert_eq!(Neighbourhood::Moore(1).offsets(dim_len), offsets);
+        }
+    }
+
+    #[test]
+    fn test_neighbourhood_radius() {
+        assert_eq!(Neighbourhood::Moore(2).offsets(1), vec![vec![1], vec![-1], vec![2], vec![-2]]);
+        assert_eq!(Neighbourhood::VonNeumann(3).offsets(1), vec![vec![1], vec![-1], vec![2], vec![-2], vec![3], vec![-3]]);
+        assert_eq!(Neighbourhood::Moore(2).len(2), 25);
+        assert_eq!(Neighbourhood::VonNeumann(2).len(2), 13);
+        assert_eq!(Neighbourhood::VonNeumann(2).len(3), 25);
+
+        // Every cell within the radius, once
+        let offsets = Neighbourhood::VonNeumann(2).offsets(2);
+        for x in -2..3_i32 {
+            for y in -2..3_i32 {
+                let expected = (x, y) != (0, 0) && x.abs() + y.abs() <= 2;
+                assert_eq!(offsets.iter().filter(|offset| **offset == vec![x, y]).count(), expected as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn test_offset_names() {
+        assert_eq!(offset_name(&[1, -1]), "(+1,-1)");
+        assert_eq!(offset_name(&[0, 2, 0]), "(0,+2,0)");
+        assert_eq!(offset_name(&[0, 0]), "0");
+        assert_eq!(compass_name(&[-1, 0]), Some("N"));
+        assert_eq!(compass_name(&[1, -1]), Some("SW"));
+        assert_eq!(compass_name(&[2, 0]), None);
+
+        // Every input of the 2D Moore neighbourhood has both names
+        let neighbourhood = Neighbourhood::Moore(1);
+        for (i, input) in neighbourhood.inputs(2).iter().enumerate() {
+            assert_eq!(offset_from_name(2, &offset_name(input)).as_ref(), Some(input));
+            assert_eq!(offset_from_name(2, compass_name(input).unwrap()).as_ref(), Some(input));
+            assert_eq!(neighbourhood.input_index(2, input), Some(i));
+        }
+        assert_eq!(offset_from_name(2, "( 1, -1 )"), Some(vec![1, -1]));
+        assert_eq!(Neighbourhood::VonNeumann(1).input_index(2, &[1, 1]), None);
+        for name in ["NE", "(+1,-1)", "(x)", "+1", "(+1"] {
+            assert_eq!(offset_from_name(1, name), None);
+        }
+
+        // Inputs by their names, compass names falling back to offsets
+        let north = neighbourhood.input_index(2, &[-1, 0]).unwrap();
+        assert_eq!(neighbourhood.input_name(2, north, InputNames::Index), north.to_string());
+        assert_eq!(neighbourhood.input_name(2, north, InputNames::Offset), "(-1,0)");
+        assert_eq!(neighbourhood.input_name(2, north, InputNames::Compass), "N");
+        assert_eq!(neighbourhood.input_name(2, 0, InputNames::Compass), "C");
+        assert_eq!(Neighbourhood::Moore(2).input_name(2, Neighbourhood::Moore(2).input_index(2, &[2, -1]).unwrap(), InputNames::Compass), "(+2,-1)");
+        assert_eq!(neighbourhood.input_name(1, 1, InputNames::Compass), "(+1)");
+        for input_names in [InputNames::Index, InputNames::Offset, InputNames::Compass] {
+            assert_eq!(InputNames::from_name(input_names.name()), Some(input_names));
+        }
+    }
+
+    #[test]
+    fn test_neighbourhood_names() {
+        for neighbourhood in [Neighbourhood::Moore(1), Neighbourhood::VonNeumann(1), Neighbourhood::Moore(2), Neighbourhood::VonNeumann(3)] {
+            assert_eq!(Neighbourhood::from_name(&neighbourhood.name()), Some(neighbourhood));
+        }
+        assert_eq!(Neighbourhood::Moore(1).name(), "moore");
+        assert_eq!(Neighbourhood::VonNeumann(3).name(), "von-neumann:3");
+        assert_eq!(Neighbourhood::from_name("moore:0"), None);
+        assert_eq!(Neighbourhood::from_name("moore:x"), None);
+        assert_eq!(Neighbourhood::from_name("hexagonal"), None);
+    }
+}
@@ -14,12 +14,10 @@ pub struct SetPermuter {
 
 impl SetPermuter {
     pub fn new(data: Vec<Vec<usize>>) -> Self {
-        let mut muter = SetPermuter {
-            data: data,
+        SetPermuter {
+            data,
             stack: vec![((0,0), (0,0))],
-        };
-
-        muter
+        }
     }
 
     pub fn reset(&mut self, data: Vec<Vec<usize>>) {
@@ -50,7 +48,7 @@ impl SetPermuter {
             return false;
         }
 
-        if self.stack.len() == 0 {
+        if self.stack.is_empty() {
             return false;
         }
 
@@ -64,12 +62,12 @@ impl SetPermuter {
     }
 
     pub fn generate_next(&mut self) {
-        if self.stack.len() == 0 {
+        if self.stack.is_empty() {
             panic!("Overflow");
         }
 
 
-        let mut last_el = self.stack.last().unwrap().clone();
+        let mut last_el = *self.stack.last().unwrap();
         self.apply_swap(last_el);
         self.stack.pop();
 
@@ -80,7 +78,7 @@ impl SetPermuter {
             self.stack.push(last_el);
 
             
-            let mut child_el = last_el.clone();
+            let mut child_el = last_el;
 
             // incr x 
             if child_el.0.1 < self.data[child_el.0.0].len() - 1 {
@@ -139,7 +137,7 @@ impl SetPermuter {
         self.apply_swap(last_el);
         self.stack.push(last_el);
 
-        let mut child_el = last_el.clone();
+        let mut child_el = last_el;
 
         // incr x 
         if child_el.0.1 < self.data[child_el.0.0].len() - 1 {
@@ -172,42 +170,58 @@ impl SetPermuter {
 
     }
 
-    fn can_incr(&self, i_j: ((usize, usize), (usize, usize))) -> bool {
-        i_j.1.0 < self.data.len() - 1 
-        || i_j.1.1 < self.data[i_j.1.0].len() - 1 
-        || i_j.0.0 < self.data.len() - 2
-        || i_j.0.1 < self.data[i_j.0.0].len() - 1
+    fn apply_swap(&mut self, i_j: ((usize, usize), (usize, usize))) {
+        let t = self.data[i_j.0.0][i_j.0.1];
+        self.data[i_j.0.0][i_j.0.1] = self.data[i_j.1.0][i_j.1.1];
+        self.data[i_j.1.0][i_j.1.1] = t;
     }
 
-    fn incr(&self, i_j: &mut ((usize, usize), (usize, usize))) {
-        if i_j.0.0 > self.data.len() - 2 || i_j.1.0 > self.data.len() - 1 {
-            panic!("Overflow");
-        }
+    // Clusters separated by ";" followed by the swap stack, e.g. "0,2;1 0:0:1:0"
+    pub fn get_state(&self) -> String {
+        let mut state = self.data.iter()
+            .map(|cluster| cluster.iter().map(|i| i.to_string()).collect::<Vec<String>>().join(","))
+            .collect::<Vec<String>>()
+            .join(";");
 
-        if i_j.1.1 < self.data[i_j.1.0].len() - 1 {
-            i_j.1.1 += 1;
+        for el in self.stack.iter() {
+            state.push_str(&format!(" {}:{}:{}:{}", el.0.0, el.0.1, el.1.0, el.1.1));
         }
-        else if i_j.1.0 < self.data.len() - 1 {
-            i_j.1.0 += 1;
-            i_j.1.1 = 0;
-        }
-        else if i_j.0.1 < self.data[i_j.0.0].len() - 1 {
-            i_j.0.1 += 1;
-            i_j.1.0 = i_j.0.0 + 1;
-            i_j.1.1 = 0;
+
+        state
+    }
+
+    pub fn from_state(state: &str) -> Result<Self, String> {
+        let mut tokens = state.split_whitespace();
+
+        let mut data: Vec<Vec<usize>> = vec![];
+        if let Some(clusters) = tokens.next() {
+            for cluster in clusters.split(';') {
+                let mut v = vec![];
+                for i in cluster.split(',').filter(|i| !i.is_empty()) {
+                    v.push(i.parse::<usize>().map_err(|_| format!("Invalid permutation index {}", i))?);
+                }
+                data.push(v);
+            }
         }
-        else if i_j.0.0 < self.data.len() - 2 {
-            i_j.0.0 += 1;
-            i_j.0.1 = 0;
-            i_j.1.0 = i_j.0.0 + 1;
-            i_j.1.1 = 0;
+
+        let mut stack = vec![];
+        for token in tokens {
+            let v: Vec<usize> = token.split(':')
+                .map(|i| i.parse::<usize>())
+                .collect::<Result<Vec<usize>, _>>()
+                .map_err(|_| format!("Invalid permutation swap {}", token))?;
+            if v.len() != 4 {
+                return Err(format!("Invalid permutation swap {}", token));
+            }
+            for (cluster, position) in [(v[0], v[1]), (v[2], v[3])] {
+                if cluster >= data.len() || position >= data[cluster].len() {
+                    return Err(format!("Permutation swap {} out of range", token));
+                }
+            }
+            stack.push(((v[0], v[1]), (v[2], v[3])));
         }
-    }
 
-    fn apply_swap(&mut self, i_j: ((usize, usize), (usize, usize))) {
-        let t = self.data[i_j.0.0][i_j.0.1];
-        self.data[i_j.0.0][i_j.0.1] = self.data[i_j.1.0][i_j.1.1];
-        self.data[i_j.1.0][i_j.1.1] = t;
+        Ok(SetPermuter { data, stack })
     }
 
     pub fn get_vector(&self) -> Vec<usize> {
@@ -222,11 +236,13 @@ impl SetPermuter {
     }
 }
 
+#[cfg(test)]
 mod test {
     use super::*;
 
 
     #[test]
+    #[allow(unreachable_code)]
     fn test_generate_set_permutations() {
         let mut permuter = SetPermuter::new(vec![vec![0, 1], vec![2, 3, 4]]);
 
@@ -248,11 +264,29 @@ mod test {
         assert_eq!(permuter.get_vector(), vec![0, 1, 2, 3]);
 
         for ex in expected {
-            assert_eq!(permuter.has_next(), true);
+            assert!(permuter.has_next());
             permuter.generate_next();
             assert_eq!(permuter.get_vector(), ex);
         }
         
-        assert_eq!(permuter.has_next(), false);
+        assert!(!permuter.has_next());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_state_round_trip() {
+        let mut permuter = SetPermuter::new(vec![]);
+        permuter.reset(vec![vec![0, 1], vec![2, 3, 4], vec![5]]);
+
+        loop {
+            assert_eq!(SetPermuter::from_state(&permuter.get_state()).unwrap(), permuter);
+            if !permuter.has_next() {
+                break;
+            }
+            permuter.generate_next();
+        }
+
+        assert_eq!(SetPermuter::from_state(&permuter.get_state()).unwrap().get_vector(), permuter.get_vector());
+        assert!(SetPermuter::from_state("0,1;2 0:0:5:0").is_err());
+        assert!(SetPermuter::from_state("0,x").is_err());
+    }
+}
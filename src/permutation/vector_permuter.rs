@@ -38,12 +38,12 @@ impl VectorPermuter {
     }
 
     pub fn has_next(&self) -> bool {
-        if self.child != None {
-            if self.child.as_ref().unwrap().has_next() {
+        if let Some(child) = &self.child {
+            if child.has_next() {
                 return true;
             }
         }
-        return self.y < self.vector.len() - 1 || self.x < self.vector.len() - 2 || self.child != None;
+        self.y < self.vector.len() - 1 || self.x < self.vector.len() - 2 || self.child.is_some()
     }
 
     pub fn generate_next(&mut self) {
@@ -52,7 +52,7 @@ impl VectorPermuter {
             return;
         }
 
-        if self.child != None {
+        if self.child.is_some() {
             if self.child.as_ref().unwrap().has_next() {
                 self.child.as_mut().unwrap().generate_next();
             }
@@ -87,8 +87,8 @@ impl VectorPermuter {
     }
 
     pub fn get_vector(&self) -> Vec<usize> {
-        if self.child != None {
-            return self.child.as_ref().unwrap().get_vector();
+        if let Some(child) = &self.child {
+            return child.get_vector();
         }
 
         let mut v = self.vector.clone();
@@ -101,6 +101,7 @@ impl VectorPermuter {
     }
 }
 
+#[cfg(test)]
 mod test {
     use super::VectorPermuter;
 
@@ -135,7 +136,7 @@ mod test {
         ];
 
         for v in ordered_vecs {
-            assert_eq!(permuter.has_next(), true);
+            assert!(permuter.has_next());
             permuter.generate_next();
             assert_eq!(permuter.get_vector(), v);
         }
@@ -12,33 +12,451 @@
 * along with result (Set/Unset/Flip current cell)
 */
 
-use crate::space::Space;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::cell::{CellValue, MAX_STATES};
+use crate::graph::expression::Expression;
 use crate::graph::op_node::OpNode;
-use crate::cell::{Cell, CellValue};
+use crate::graph::sum_of_products::SumOfProducts;
+use crate::graph::truth_table::TruthTable;
+use crate::neighbourhood::{InputNames, Neighbourhood};
+use crate::permutation::set_permuter::SetPermuter;
 
-#[derive(Debug, PartialEq, Clone)]
+// Rules are printed with their number up to the Moore neighbourhood of 2D
+const MAX_NUMBERED_INPUTS: usize = 9;
+
+// Rule numbers of families are u128, the largest being 2^127 - 1
+const MAX_FAMILY_BITS: usize = 127;
+
+// Version of the format of Rule::to_text
+const TEXT_VERSION: usize = 1;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum Action {
     Set,
     Unset,
-    Flip
+    // Moves the cell to the next state, the last state going back to Unset
+    Flip,
+    // Moves the cell to a state past Set, with rules of more than 2 states
+    State(u8)
+}
+
+// Actions the enumeration goes through for each condition. By default Set then
+// Flip, so a rule either births or kills cells. Unset adds rules unsetting cells,
+// and else adds an action for where the condition is false, so that a rule such
+// as "Set, else Unset" births and kills cells
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Actions {
+    pub unset: bool,
+    pub else_action: bool
+}
+
+impl Actions {
+    pub fn new() -> Self {
+        Actions {
+            unset: false,
+            else_action: false
+        }
+    }
+
+    // Actions separated by ",", then "else", e.g. "set,flip,unset,else"
+    pub fn name(&self) -> String {
+        let mut names = vec!["set", "flip"];
+        if self.unset {
+            names.push("unset");
+        }
+        if self.else_action {
+            names.push("else");
+        }
+        names.join(",")
+    }
+
+    pub fn from_name(name: &str) -> Option<Actions> {
+        let mut actions = Actions::new();
+        let names: Vec<&str> = name.split(',').map(|el| el.trim()).collect();
+        if names.len() < 2 || names[0] != "set" || names[1] != "flip" {
+            return None;
+        }
+        for el in names.iter().skip(2) {
+            match *el {
+                "unset" if !actions.unset && !actions.else_action => actions.unset = true,
+                "else" if !actions.else_action => actions.else_action = true,
+                _ => return None
+            }
+        }
+        Some(actions)
+    }
+
+    // Action and else action of each rule of a condition, in enumeration order
+    pub fn pairs(&self) -> Vec<(Action, Option<Action>)> {
+        self.pairs_with_states(2)
+    }
+
+    // Rules of more states also move cells to each state past Set
+    pub fn pairs_with_states(&self, states: usize) -> Vec<(Action, Option<Action>)> {
+        let mut actions = vec![Action::Set, Action::Flip];
+        if self.unset {
+            actions.push(Action::Unset);
+        }
+        actions.extend((2..states).map(|state| Action::State(state as u8)));
+
+        let mut pairs = vec![];
+        for action in actions.iter() {
+            pairs.push((*action, None));
+            if self.else_action {
+                // The same action on both sides ignores the condition
+                for else_action in actions.iter().filter(|el| *el != action) {
+                    pairs.push((*action, Some(*else_action)));
+                }
+            }
+        }
+        pairs
+    }
+}
+
+impl Default for Actions {
+    fn default() -> Self {
+        Actions::new()
+    }
+}
+
+// Families of rules enumerated by their number instead of as OpNode trees.
+// A rule of a family gives the next value of the cell, which is bit k of its number:
+// - elementary: 1D rules, k reading the cells as binary digits from the leftmost
+//   one, so rule 30 is Wolfram's rule 30
+// - totalistic: k is the number of set cells, the cell itself included
+// - outer-totalistic: k is twice the number of set neighbours, plus 1 when the
+//   cell is set, as in Life-like rules. Life is rule 224 of the 2D Moore neighbourhood
+// - generations: outer-totalistic rules of any number of states, counting the
+//   neighbours in state Set. Set cells which don't survive go through every
+//   state past Set before being unset, as in Brian's Brain (B2/S/3)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Family {
+    // Conditions enumerated as OpNode trees, with an action
+    Tree,
+    Elementary,
+    Totalistic,
+    OuterTotalistic,
+    Generations
+}
+
+impl Family {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Family::Tree => "tree",
+            Family::Elementary => "elementary",
+            Family::Totalistic => "totalistic",
+            Family::OuterTotalistic => "outer-totalistic",
+            Family::Generations => "generations"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Family> {
+        match name {
+            "tree" => Some(Family::Tree),
+            "elementary" => Some(Family::Elementary),
+            "totalistic" => Some(Family::Totalistic),
+            "outer-totalistic" => Some(Family::OuterTotalistic),
+            "generations" => Some(Family::Generations),
+            _ => None
+        }
+    }
+
+    // Number of bits of the family's rule numbers, there are 2^bits rules
+    pub fn rule_bits(&self, dim_len: usize, neighbourhood: Neighbourhood) -> Result<usize, String> {
+        let inputs = neighbourhood.len(dim_len);
+        let bits = match self {
+            Family::Tree => return Err("Rules of the tree family have no number".to_string()),
+            Family::Elementary => {
+                if dim_len != 1 {
+                    return Err(format!("Elementary rules are 1D, not {}D", dim_len));
+                }
+                1_usize.checked_shl(inputs as u32).unwrap_or(usize::MAX)
+            },
+            Family::Totalistic => inputs + 1,
+            Family::OuterTotalistic | Family::Generations => 2 * inputs
+        };
+
+        if bits > MAX_FAMILY_BITS {
+            return Err(format!("The {} neighbourhood in {}D has too many {} rules", neighbourhood.name(), dim_len, self.name()));
+        }
+        Ok(bits)
+    }
+
+    // Whether rules of the family can read the neighbourhood in the dimension
+    pub fn supports(&self, dim_len: usize, neighbourhood: Neighbourhood) -> Result<(), String> {
+        match self {
+            Family::Tree => Ok(()),
+            _ => self.rule_bits(dim_len, neighbourhood).map(|_| ())
+        }
+    }
+
+    // Whether rules of the family can have that many states
+    pub fn supports_states(&self, states: usize) -> Result<(), String> {
+        if !(2..=MAX_STATES).contains(&states) {
+            return Err(format!("Cells have between 2 and {} states, not {}", MAX_STATES, states));
+        }
+
+        match self {
+            Family::Tree | Family::Generations => Ok(()),
+            _ if states == 2 => Ok(()),
+            _ => Err(format!("Rules of the {} family only have 2 states", self.name()))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Condition {
+    // Expression of the inputs, the action applying where it's true
+    Tree(OpNode),
+    // Rule of a family, by its number
+    Family(Family, u128),
+    // Hand-written condition, which isn't enumerated
+    Expression(Expression)
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct Rule {
-    condition: OpNode,
-    pub result: Action
+    condition: Condition,
+    // Always Flip for rules of a family, flipping the cell where its next value differs
+    pub result: Action,
+    // Applies where the condition is false, the cell is left as it is without one
+    pub else_result: Option<Action>,
+    // Actions enumerated for each condition
+    actions: Actions,
+    // Number of states of the cells, and the state the inputs of a tree condition
+    // are tested for. Binary rules test whether the inputs are set
+    states: usize,
+    tested: CellValue,
+    neighbourhood: Neighbourhood,
+    dim_len: usize,
+    // How the inputs of the condition are printed, and whether the minimal sum of
+    // products is, see Rule::minimise
+    input_names: InputNames,
+    print_minimal: bool
 }
 
 impl Rule {
     pub fn new(dim_len: usize) -> Self {
+        Rule::with_neighbourhood(dim_len, Neighbourhood::Moore(1))
+    }
+
+    // For spacial dimension of any number there are 3^N surrounding cells
+    // (including not just neighbours), but only 2N + 1 if the rule only considers
+    // the cell and its direct neighbours, so many more dimensions can be explored
+    pub fn with_neighbourhood(dim_len: usize, neighbourhood: Neighbourhood) -> Self {
+        Rule::with_actions(dim_len, neighbourhood, Actions::new())
+    }
+
+    pub fn with_actions(dim_len: usize, neighbourhood: Neighbourhood, actions: Actions) -> Self {
+        let (result, else_result) = actions.pairs()[0];
         Rule {
-            // 3 ain't a magic number
-            // for spacial dimension of any number
-            // there are 3^N surrounding cells (including not just neighbours)
-            // What if the rule in reality only considers it's neighbours ? If that's the case
-            // There's 2*N nodes only and we can potentially explore many more dimensions
-            condition: OpNode::new(0, (3 as usize).pow(dim_len as u32) - 1),
-            result: Action::Set
+            condition: Condition::Tree(OpNode::new(0, neighbourhood.len(dim_len) - 1)),
+            result,
+            else_result,
+            actions,
+            states: 2,
+            tested: CellValue::Set,
+            neighbourhood,
+            dim_len,
+            input_names: InputNames::Index,
+            print_minimal: false
+        }
+    }
+
+    // First rule of the family, panics if the family doesn't support the
+    // neighbourhood in the dimension, see Family::supports
+    pub fn with_family(dim_len: usize, neighbourhood: Neighbourhood, family: Family) -> Self {
+        match Rule::from_number(dim_len, neighbourhood, family, 0) {
+            Ok(rule) => rule,
+            Err(_) if family == Family::Tree => Rule::with_neighbourhood(dim_len, neighbourhood),
+            Err(err) => panic!("{}", err)
+        }
+    }
+
+    // First rule enumerated, the actions are those of the tree family's conditions
+    pub fn first(dim_len: usize, neighbourhood: Neighbourhood, family: Family, actions: Actions) -> Self {
+        match family {
+            Family::Tree => Rule::with_actions(dim_len, neighbourhood, actions),
+            _ => Rule::with_family(dim_len, neighbourhood, family)
+        }
+    }
+
+    // Rule of a family by its number
+    pub fn from_number(dim_len: usize, neighbourhood: Neighbourhood, family: Family, number: u128) -> Result<Self, String> {
+        let bits = family.rule_bits(dim_len, neighbourhood)?;
+        if number >> bits != 0 {
+            return Err(format!("There are only 2^{} {} rules, {} isn't one of them", bits, family.name(), number));
+        }
+
+        Ok(Rule {
+            condition: Condition::Family(family, number),
+            result: Action::Flip,
+            else_result: None,
+            actions: Actions::new(),
+            states: 2,
+            tested: CellValue::Set,
+            neighbourhood,
+            dim_len,
+            input_names: InputNames::Index,
+            print_minimal: false
+        })
+    }
+
+    // Rule of a hand-written condition, see Expression::parse, applying the action
+    // where it holds and the else action, if any, elsewhere
+    pub fn from_expression(dim_len: usize, neighbourhood: Neighbourhood, expression: &str, result: Action, else_result: Option<Action>) -> Result<Self, String> {
+        Ok(Rule {
+            condition: Condition::Expression(Expression::parse(expression, dim_len, neighbourhood)?),
+            result,
+            else_result,
+            actions: Actions::new(),
+            states: 2,
+            tested: CellValue::Set,
+            neighbourhood,
+            dim_len,
+            input_names: InputNames::Index,
+            print_minimal: false
+        })
+    }
+
+    // First rule of the enumeration of rules of as many states, panics if
+    // the family doesn't support them, see Family::supports_states
+    pub fn with_states(mut self, states: usize) -> Self {
+        if let Err(err) = self.get_family().supports_states(states) {
+            panic!("{}", err);
+        }
+
+        self.states = states;
+        if let Condition::Tree(_) = self.condition {
+            self.tested = CellValue::Set;
+            (self.result, self.else_result) = self.actions.pairs_with_states(states)[0];
+        }
+        self
+    }
+
+    // Prints the inputs of the condition by their names. Other than by index, the tree is
+    // printed with its evaluation permutation applied, each leaf being the input it reads
+    pub fn with_input_names(mut self, input_names: InputNames) -> Self {
+        self.input_names = input_names;
+        self
+    }
+
+    // Prints the minimal sum of products of tree and hand-written rules. Off by default,
+    // as minimising every rule printed while exploring would slow the search
+    pub fn with_minimal(mut self, print_minimal: bool) -> Self {
+        self.print_minimal = print_minimal;
+        self
+    }
+
+    // Outer-totalistic rule of a Life-like rulestring: the numbers of set neighbours
+    // a cell is born with, then those it survives with, e.g. "B3/S23" for Life or
+    // "B2/S" for Seeds. In neighbourhoods of 10 neighbours or more, numbers are
+    // separated by commas, e.g. "B5,6,7/S10". A number of states after them makes
+    // it a rule of the generations family, e.g. "B2/S/3" for Brian's Brain
+    pub fn from_rulestring(dim_len: usize, neighbourhood: Neighbourhood, rulestring: &str) -> Result<Self, String> {
+        let neighbours = neighbourhood.len(dim_len) - 1;
+        let mut birth = None;
+        let mut survival = None;
+        let mut states = None;
+
+        for part in rulestring.trim().split('/') {
+            if birth.is_some() && survival.is_some() && states.is_none() {
+                let count = part.trim_start_matches(['C', 'c']).parse::<usize>().ok()
+                    .filter(|count| Family::Generations.supports_states(*count).is_ok())
+                    .ok_or(format!("Invalid number of states {} in {}", part, rulestring))?;
+                states = Some(count);
+                continue;
+            }
+
+            let (counts, bit) = match part.chars().next() {
+                Some('B') | Some('b') => (&mut birth, 0),
+                Some('S') | Some('s') => (&mut survival, 1),
+                _ => return Err(format!("Invalid rulestring {}, expected B<counts>/S<counts>", rulestring))
+            };
+            if counts.is_some() {
+                return Err(format!("Invalid rulestring {}, expected B<counts>/S<counts>", rulestring));
+            }
+
+            let numbers: Vec<String> = if neighbours >= 10 {
+                part[1..].split(',').filter(|el| !el.is_empty()).map(|el| el.to_string()).collect()
+            }
+            else {
+                part[1..].chars().map(|c| c.to_string()).collect()
+            };
+
+            let mut number: u128 = 0;
+            for el in numbers {
+                let count = el.trim().parse::<usize>().ok()
+                    .filter(|count| *count <= neighbours)
+                    .ok_or(format!("Invalid number of neighbours {} in {}, there are {}", el, rulestring, neighbours))?;
+                number |= 1 << (2 * count + bit);
+            }
+            *counts = Some(number);
+        }
+
+        match (birth, survival, states) {
+            (Some(birth), Some(survival), None) => Rule::from_number(dim_len, neighbourhood, Family::OuterTotalistic, birth | survival),
+            (Some(birth), Some(survival), Some(states)) => Ok(Rule::from_number(dim_len, neighbourhood, Family::Generations, birth | survival)?.with_states(states)),
+            _ => Err(format!("Invalid rulestring {}, expected B<counts>/S<counts>", rulestring))
+        }
+    }
+
+    // Rulestring of an outer-totalistic or generations rule, see Rule::from_rulestring
+    pub fn get_rulestring(&self) -> Option<String> {
+        let number = match self.condition {
+            Condition::Family(Family::OuterTotalistic | Family::Generations, number) => number,
+            _ => return None
+        };
+
+        let neighbours = self.neighbourhood.len(self.dim_len) - 1;
+        let separator = if neighbours >= 10 { "," } else { "" };
+        let counts = |bit: usize| {
+            (0..neighbours + 1)
+                .filter(|count| number >> (2 * count + bit) & 1 == 1)
+                .map(|count| count.to_string())
+                .collect::<Vec<String>>()
+                .join(separator)
+        };
+
+        match self.get_family() {
+            Family::Generations => Some(format!("B{}/S{}/{}", counts(0), counts(1), self.states)),
+            _ => Some(format!("B{}/S{}", counts(0), counts(1)))
+        }
+    }
+
+    pub fn get_dimension(&self) -> usize {
+        self.dim_len
+    }
+
+    // Cells the condition reads, in the order of its variables
+    pub fn get_neighbourhood(&self) -> Neighbourhood {
+        self.neighbourhood
+    }
+
+    pub fn get_actions(&self) -> Actions {
+        self.actions
+    }
+
+    pub fn get_states(&self) -> usize {
+        self.states
+    }
+
+    pub fn get_family(&self) -> Family {
+        match self.condition {
+            Condition::Tree(_) | Condition::Expression(_) => Family::Tree,
+            Condition::Family(family, _) => family
+        }
+    }
+
+    // Number of a rule of a family
+    pub fn get_number(&self) -> Option<u128> {
+        match self.condition {
+            Condition::Tree(_) | Condition::Expression(_) => None,
+            Condition::Family(_, number) => Some(number)
         }
     }
 
@@ -48,41 +466,914 @@ impl Rule {
         }
 
         self.print();
-        println!("Cluster: {:?}", self.condition.get_clustered_variables());
+        if let Condition::Tree(node) = &self.condition {
+            println!("Cluster: {:?}", node.get_clustered_variables());
+        }
     }
 
     pub fn print(&self) {
-        print!("Rule: ");
-        self.condition.print();
-        println!("");
-        println!("Permutation: {:?}", self.condition.eval_permutation.get_vector());
-        println!("Action: {:?}", self.result);
+        print!("{}", self);
     }
 
     pub fn has_next_eval_permutation(&self) -> bool {
-        self.condition.has_next_eval_permutation()
+        match &self.condition {
+            Condition::Tree(node) => node.has_next_eval_permutation(),
+            Condition::Family(..) | Condition::Expression(_) => false
+        }
     }
 
     pub fn generate_next_eval_permutation(&mut self) {
         // Only called on root node
-        self.condition.generate_next_eval_permutation();
+        if let Condition::Tree(node) = &mut self.condition {
+            node.generate_next_eval_permutation();
+        }
+    }
+
+    // A candidate is a rule together with one of its evaluation permutations,
+    // stepped through in the same order StateExplorer explores them
+    pub fn has_next_candidate(&self) -> bool {
+        self.has_next_eval_permutation() || self.has_next()
+    }
+
+    pub fn generate_next_candidate(&mut self) {
+        if self.has_next_eval_permutation() {
+            self.generate_next_eval_permutation();
+        }
+        else {
+            self.generate_next();
+        }
     }
 
     pub fn has_next(&self) -> bool {
-        self.result == Action::Set || self.condition.has_next()
+        match &self.condition {
+            Condition::Tree(node) => self.next_actions().is_some() || self.tested.state() + 1 < self.states || node.has_next(),
+            Condition::Family(family, number) => {
+                // Validated when the rule was created
+                let bits = family.rule_bits(self.dim_len, self.neighbourhood).unwrap();
+                *number < u128::MAX >> (128 - bits)
+            },
+            Condition::Expression(_) => false
+        }
     }
 
     pub fn generate_next(&mut self) {
-        if self.result == Action::Set {
-            self.result = Action::Flip;
+        match &mut self.condition {
+            Condition::Family(_, number) => {
+                *number += 1;
+                return;
+            },
+            Condition::Expression(_) => panic!("Rules of hand-written conditions aren't enumerated"),
+            Condition::Tree(_) => {}
+        }
+
+        if let Some((result, else_result)) = self.next_actions() {
+            self.result = result;
+            self.else_result = else_result;
             return;
         }
-        
-        self.result = Action::Set;
-        self.condition.generate_next();
+
+        // Then the states tested, then the condition
+        (self.result, self.else_result) = self.actions.pairs_with_states(self.states)[0];
+        if self.tested.state() + 1 < self.states {
+            // Testing another state is another condition, with its own permutations
+            self.tested = CellValue::new(self.tested.state() + 1);
+            if let Condition::Tree(node) = &mut self.condition {
+                node.reset_permuter();
+            }
+            return;
+        }
+        self.tested = CellValue::Set;
+        if let Condition::Tree(node) = &mut self.condition {
+            node.generate_next();
+        }
+    }
+
+    // Actions of the next rule of the same condition
+    fn next_actions(&self) -> Option<(Action, Option<Action>)> {
+        let pairs = self.actions.pairs_with_states(self.states);
+        let i = pairs.iter().position(|pair| *pair == (self.result, self.else_result))?;
+        pairs.get(i + 1).copied()
     }
 
+    // Whether the action applies to the cell. The cell itself is v[0]
     pub fn evaluate(&self, v: &Vec<bool>) -> bool {
-        self.condition.evaluate(v, true)
+        match &self.condition {
+            Condition::Tree(node) => node.evaluate(v, true),
+            Condition::Expression(expression) => expression.evaluate(v),
+            Condition::Family(..) => self.next_value(v) != v[0]
+        }
+    }
+
+    // Value of the cell in the next generation
+    pub fn next_value(&self, v: &[bool]) -> bool {
+        let set_neighbours = v[1..].iter().filter(|el| **el).count();
+        match &self.condition {
+            Condition::Tree(node) => {
+                let action = if node.evaluate(&v.to_vec(), true) {
+                    Some(self.result)
+                }
+                else {
+                    self.else_result
+                };
+                self.apply_action(action, CellValue::from(v[0])).is_set()
+            },
+            Condition::Expression(expression) => {
+                let action = if expression.evaluate(v) { Some(self.result) } else { self.else_result };
+                self.apply_action(action, CellValue::from(v[0])).is_set()
+            },
+            Condition::Family(Family::Elementary, _) => self.evaluate_elementary(v),
+            Condition::Family(Family::Totalistic, _) => self.evaluate_totalistic(set_neighbours + v[0] as usize),
+            Condition::Family(Family::OuterTotalistic, _) => self.evaluate_outer_totalistic(v[0], set_neighbours),
+            Condition::Family(Family::Generations, _) => self.evaluate_generations(CellValue::from(v[0]), set_neighbours) == CellValue::Set,
+            Condition::Family(Family::Tree, _) => unreachable!()
+        }
+    }
+
+    // State of the cell in the next generation, from the states of its inputs
+    pub fn next_state(&self, v: &[CellValue]) -> CellValue {
+        match &self.condition {
+            Condition::Tree(node) => {
+                let inputs: Vec<bool> = v.iter().map(|el| *el == self.tested).collect();
+                let action = if node.evaluate(&inputs, true) {
+                    Some(self.result)
+                }
+                else {
+                    self.else_result
+                };
+                self.apply_action(action, v[0])
+            },
+            Condition::Expression(expression) => {
+                let inputs: Vec<bool> = v.iter().map(|el| *el == self.tested).collect();
+                let action = if expression.evaluate(&inputs) { Some(self.result) } else { self.else_result };
+                self.apply_action(action, v[0])
+            },
+            Condition::Family(Family::Generations, _) => {
+                self.evaluate_generations(v[0], v[1..].iter().filter(|el| **el == CellValue::Set).count())
+            },
+            Condition::Family(..) => CellValue::from(self.next_value(&v.iter().map(|el| el.is_set()).collect::<Vec<bool>>()))
+        }
+    }
+
+    // Whether the rule does anything to a cell with these inputs: its condition
+    // holds, or it has an else action
+    pub fn acts(&self, v: &[CellValue]) -> bool {
+        match &self.condition {
+            Condition::Tree(node) => {
+                self.else_result.is_some() || node.evaluate(&v.iter().map(|el| *el == self.tested).collect(), true)
+            },
+            Condition::Expression(expression) => {
+                self.else_result.is_some() || expression.evaluate(&v.iter().map(|el| *el == self.tested).collect::<Vec<bool>>())
+            },
+            Condition::Family(..) => self.next_state(v) != v[0]
+        }
+    }
+
+    fn apply_action(&self, action: Option<Action>, value: CellValue) -> CellValue {
+        match action {
+            Some(Action::Set) => CellValue::Set,
+            Some(Action::Unset) => CellValue::Unset,
+            Some(Action::Flip) => CellValue::new((value.state() + 1) % self.states),
+            Some(Action::State(state)) => CellValue::new(state as usize),
+            None => value
+        }
+    }
+
+    // Next value under an elementary rule. 1D inputs are the cell, then the cells
+    // at +1, -1, +2, -2 .., the leftmost one being the most significant digit
+    pub fn evaluate_elementary(&self, v: &[bool]) -> bool {
+        let radius = (v.len() / 2) as i32;
+        let config = v.iter().enumerate().fold(0, |config, (i, value)| {
+            let i = i as i32;
+            let coordinate = if i % 2 == 1 { (i + 1) / 2 } else { -i / 2 };
+            config | (*value as usize) << (radius - coordinate)
+        });
+        self.number_bit(config)
+    }
+
+    // Next value under a totalistic rule, from the number of set cells, the cell itself included
+    pub fn evaluate_totalistic(&self, set_cells: usize) -> bool {
+        self.number_bit(set_cells)
     }
-}
\ No newline at end of file
+
+    // Next value under an outer-totalistic rule
+    pub fn evaluate_outer_totalistic(&self, value: bool, set_neighbours: usize) -> bool {
+        self.number_bit(2 * set_neighbours + value as usize)
+    }
+
+    // Next state under a generations rule, counting the neighbours in state Set
+    pub fn evaluate_generations(&self, value: CellValue, set_neighbours: usize) -> CellValue {
+        match value {
+            CellValue::Unset => CellValue::from(self.number_bit(2 * set_neighbours)),
+            CellValue::Set if self.number_bit(2 * set_neighbours + 1) => CellValue::Set,
+            _ => CellValue::new((value.state() + 1) % self.states)
+        }
+    }
+
+    fn number_bit(&self, bit: usize) -> bool {
+        match self.condition {
+            Condition::Family(_, number) => number >> bit & 1 == 1,
+            Condition::Tree(_) | Condition::Expression(_) => panic!("Rules of the tree family have no number")
+        }
+    }
+
+    // Table of the condition over the inputs, the cell itself being input 0
+    pub fn get_condition_truth_table(&self) -> TruthTable {
+        match &self.condition {
+            Condition::Tree(node) => TruthTable::from_op_node(node),
+            Condition::Expression(expression) => TruthTable::from_expression(expression, self.neighbourhood.len(self.dim_len)),
+            Condition::Family(..) => TruthTable::from_fn(self.neighbourhood.len(self.dim_len), |values| self.evaluate(values))
+        }
+    }
+
+    // Minimal sum of products of a tree or hand-written rule: of the next value of the
+    // cell for binary rules, which leaves out the inputs the rule doesn't depend on, and of
+    // the condition, over the inputs being in the state tested, for rules of more states.
+    // None for rules of other families, which their number already describes
+    pub fn minimise(&self) -> Option<SumOfProducts> {
+        match self.condition {
+            Condition::Family(..) => None,
+            _ if self.states == 2 => Some(SumOfProducts::minimise(&self.get_truth_table())),
+            _ => Some(SumOfProducts::minimise(&self.get_condition_truth_table()))
+        }
+    }
+
+    // Table of the next value of the cell, combining the condition with the action
+    pub fn get_truth_table(&self) -> TruthTable {
+        let inputs = self.neighbourhood.len(self.dim_len);
+        if matches!(self.condition, Condition::Family(..)) || self.states > 2 {
+            return TruthTable::from_fn(inputs, |values| self.next_value(values));
+        }
+
+        // Each action gives the next value from the value of the cell, the action
+        // applying where the condition holds and the else action elsewhere
+        let cell = TruthTable::input(inputs, 0);
+        let next = |action: Option<Action>| {
+            let value = |value: CellValue| TruthTable::constant(inputs, self.apply_action(action, value).is_set());
+            cell.select(&value(CellValue::Set), &value(CellValue::Unset))
+        };
+        self.get_condition_truth_table().select(&next(Some(self.result)), &next(self.else_result))
+    }
+
+    // Wolfram-style rule number: the table of the next value, configurations
+    // numbered with the inputs ordered by coordinates, the lowest coordinates
+    // being the most significant bit. In 1D that's left, centre, right, as in
+    // elementary rule 30 or 110
+    pub fn get_rule_number(&self) -> String {
+        let inputs = self.neighbourhood.inputs(self.dim_len);
+        let mut sorted: Vec<usize> = (0..inputs.len()).collect();
+        sorted.sort_by(|a, b| inputs[*a].cmp(&inputs[*b]));
+
+        // Bit 0 of the number's configurations is the highest coordinate
+        sorted.reverse();
+        self.get_truth_table().reorder(&sorted).to_number()
+    }
+
+    // Enumeration state, so exploration can continue from this rule later on.
+    // Action (and else action), then condition and evaluation permutation states
+    // separated by "/", then the enumerated actions unless they're the default,
+    // then the number of states and the state tested for rules of more than 2 states.
+    // Hand-written conditions are "expression" and the condition instead of the
+    // tree and permutation, e.g. "Set / expression ( 0 & !1 )".
+    // Rules of a family are the family and number, e.g. "totalistic 12", then
+    // their number of states if more than 2, e.g. "generations 12 states 3"
+    pub fn get_state(&self) -> String {
+        let condition = match &self.condition {
+            Condition::Tree(node) => format!("{} / {}", node.get_state(), node.eval_permutation.get_state()),
+            Condition::Expression(expression) => format!("expression {}", expression),
+            Condition::Family(family, number) if self.states > 2 => return format!("{} {} states {}", family.name(), number, self.states),
+            Condition::Family(family, number) => return format!("{} {}", family.name(), number)
+        };
+
+        let mut state = format!("{:?}", self.result);
+        if let Some(else_result) = self.else_result {
+            state.push_str(&format!(" else {:?}", else_result));
+        }
+        state.push_str(&format!(" / {}", condition));
+        if self.actions != Actions::new() {
+            state.push_str(&format!(" / {}", self.actions.name()));
+        }
+        if self.states > 2 {
+            state.push_str(&format!(" / states {} {}", self.states, self.tested.state()));
+        }
+        state
+    }
+
+    pub fn from_state(dim_len: usize, neighbourhood: Neighbourhood, state: &str) -> Result<Self, String> {
+        let parse_states = |states: &str| states.trim().parse::<usize>().ok()
+            .filter(|states| (3..=MAX_STATES).contains(states))
+            .ok_or(format!("Invalid number of states {}", states));
+
+        if !state.contains('/') {
+            let (state, states) = match state.split_once(" states ") {
+                Some((state, states)) => (state, Some(parse_states(states)?)),
+                None => (state, None)
+            };
+            let (family, number) = state.trim().split_once(' ').ok_or(format!("Invalid rule state {}", state))?;
+            let family = Family::from_name(family).filter(|family| *family != Family::Tree)
+                .ok_or(format!("Unknown rule family {}", family))?;
+            let number = number.trim().parse::<u128>().map_err(|_| format!("Invalid rule number {}", number))?;
+            let rule = Rule::from_number(dim_len, neighbourhood, family, number)?;
+            return match states {
+                Some(states) => {
+                    family.supports_states(states)?;
+                    Ok(rule.with_states(states))
+                },
+                None => Ok(rule)
+            };
+        }
+
+        let mut parts: Vec<&str> = state.split('/').collect();
+        let (states, tested) = match parts.last().and_then(|part| part.trim().strip_prefix("states ")) {
+            Some(states) => {
+                let (states, tested) = states.trim().split_once(' ').ok_or(format!("Invalid rule state {}", state))?;
+                let states = parse_states(states)?;
+                let tested = tested.trim().parse::<usize>().ok().filter(|tested| (1..states).contains(tested))
+                    .ok_or(format!("Invalid state {} tested by the condition", tested))?;
+                parts.pop();
+                (states, CellValue::new(tested))
+            },
+            None => (2, CellValue::Set)
+        };
+        let expression = parts.get(1).and_then(|part| part.trim().strip_prefix("expression "));
+        if expression.is_none() && parts.len() != 3 && parts.len() != 4 {
+            return Err(format!("Invalid rule state {}", state));
+        }
+        if expression.is_some() && parts.len() != 2 && parts.len() != 3 {
+            return Err(format!("Invalid rule state {}", state));
+        }
+
+        let parse_action = |action: &str| match action.trim() {
+            "Set" => Ok(Action::Set),
+            "Unset" => Ok(Action::Unset),
+            "Flip" => Ok(Action::Flip),
+            action => action.strip_prefix("State(").and_then(|state| state.strip_suffix(')'))
+                .and_then(|state| state.parse::<usize>().ok())
+                .filter(|state| (2..states).contains(state))
+                .map(|state| Action::State(state as u8))
+                .ok_or(format!("Unknown action {}", action))
+        };
+        let (result, else_result) = match parts[0].split_once(" else ") {
+            Some((result, else_result)) => (parse_action(result)?, Some(parse_action(else_result)?)),
+            None => (parse_action(parts[0])?, None)
+        };
+        let actions = match parts.get(if expression.is_some() { 2 } else { 3 }) {
+            Some(actions) => Actions::from_name(actions.trim()).ok_or(format!("Unknown actions {}", actions.trim()))?,
+            None => Actions::new()
+        };
+
+        if let Some(expression) = expression {
+            return Ok(Rule {
+                condition: Condition::Expression(Expression::parse(expression, dim_len, neighbourhood)?),
+                result,
+                else_result,
+                actions,
+                states,
+                tested,
+                neighbourhood,
+                dim_len,
+                input_names: InputNames::Index,
+                print_minimal: false
+            });
+        }
+
+        let mut condition = OpNode::from_state(0, neighbourhood.len(dim_len) - 1, parts[1])?;
+        condition.eval_permutation = SetPermuter::from_state(parts[2])?;
+
+        if condition.eval_permutation.get_vector().len() != condition.len() {
+            return Err(format!("Permutation {} doesn't match the condition", parts[2].trim()));
+        }
+
+        Ok(Rule {
+            condition: Condition::Tree(condition),
+            result,
+            else_result,
+            actions,
+            states,
+            tested,
+            neighbourhood,
+            dim_len,
+            input_names: InputNames::Index,
+            print_minimal: false
+        })
+    }
+
+    /*
+     * Rule as text, read back by Rule::from_text, one entry per line:
+     *
+     *   rule 1
+     *   dimension 2
+     *   neighbourhood moore
+     *   family tree
+     *   states 2
+     *   condition & 0 v | 1 ! v
+     *   permutation 0;1,2
+     *   tested 1
+     *   action Set
+     *   else Unset
+     *   actions set,flip,else
+     *   expression ( 0 & ( !1 | 2 ) )
+     *
+     * The condition is the tree, see OpNode::get_state, and the permutation the
+     * order it's evaluated in, see SetPermuter::get_state. else is only there for
+     * rules with an else action. The expression is the condition as printed, for
+     * reading the file, and has to match the tree. Rules of a hand-written
+     * condition have no tree or permutation, their expression is the condition.
+     * Rules of a family have their number instead, e.g. "number 224", after the states
+     */
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![
+            format!("rule {}", TEXT_VERSION),
+            format!("dimension {}", self.dim_len),
+            format!("neighbourhood {}", self.neighbourhood.name()),
+            format!("family {}", self.get_family().name()),
+            format!("states {}", self.states)
+        ];
+
+        match &self.condition {
+            Condition::Tree(node) => {
+                lines.push(format!("condition {}", node.get_state()));
+                lines.push(format!("permutation {}", node.eval_permutation.get_state()));
+                lines.push(format!("tested {}", self.tested.state()));
+                lines.push(format!("action {:?}", self.result));
+                if let Some(else_result) = self.else_result {
+                    lines.push(format!("else {:?}", else_result));
+                }
+                lines.push(format!("actions {}", self.actions.name()));
+                lines.push(format!("expression {}", node));
+            },
+            Condition::Expression(expression) => {
+                lines.push(format!("tested {}", self.tested.state()));
+                lines.push(format!("action {:?}", self.result));
+                if let Some(else_result) = self.else_result {
+                    lines.push(format!("else {:?}", else_result));
+                }
+                lines.push(format!("expression {}", expression));
+            },
+            Condition::Family(_, number) => lines.push(format!("number {}", number))
+        }
+
+        lines.join("\n") + "\n"
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut entries: Vec<(&str, &str)> = vec![];
+        for line in text.lines().map(|line| line.trim()).filter(|line| !line.is_empty()) {
+            let (key, value) = line.split_once(' ').ok_or(format!("Invalid rule entry {}", line))?;
+            if entries.iter().any(|(other, _)| *other == key) {
+                return Err(format!("Duplicate rule entry {}", line));
+            }
+            entries.push((key, value.trim()));
+        }
+        let entry = |key: &str| entries.iter().find(|(other, _)| *other == key).map(|(_, value)| *value);
+        let required = |key: &str| entry(key).ok_or(format!("Missing rule entry {}", key));
+
+        if let Some((key, _)) = entries.iter().find(|(key, _)| !["rule", "dimension", "neighbourhood", "family", "states", "condition",
+            "permutation", "tested", "action", "else", "actions", "expression", "number"].contains(key)) {
+            return Err(format!("Unknown rule entry {}", key));
+        }
+        if required("rule")? != TEXT_VERSION.to_string() {
+            return Err(format!("Unsupported rule version {}", required("rule")?));
+        }
+
+        let dim_len = required("dimension")?.parse::<usize>().ok().filter(|dim_len| *dim_len > 0)
+            .ok_or(format!("Invalid dimension {}", required("dimension")?))?;
+        let neighbourhood = Neighbourhood::from_name(required("neighbourhood")?)
+            .ok_or(format!("Unknown neighbourhood {}", required("neighbourhood")?))?;
+        let family = Family::from_name(required("family")?)
+            .ok_or(format!("Unknown rule family {}", required("family")?))?;
+        let states = match entry("states") {
+            Some(states) => states.parse::<usize>().map_err(|_| format!("Invalid number of states {}", states))?,
+            None => 2
+        };
+        family.supports(dim_len, neighbourhood)?;
+
+        // The entries make up the enumeration state, see Rule::get_state
+        if family != Family::Tree {
+            let mut state = format!("{} {}", family.name(), required("number")?);
+            if states != 2 {
+                state.push_str(&format!(" states {}", states));
+            }
+            return Rule::from_state(dim_len, neighbourhood, &state);
+        }
+
+        let mut state = required("action")?.to_string();
+        if let Some(else_result) = entry("else") {
+            state.push_str(&format!(" else {}", else_result));
+        }
+        // Without a tree, the expression is the condition
+        match entry("condition") {
+            Some(condition) => state.push_str(&format!(" / {} / {}", condition, required("permutation")?)),
+            None => state.push_str(&format!(" / expression {}", required("expression")?))
+        }
+        state.push_str(&format!(" / {}", entry("actions").unwrap_or("set,flip")));
+        if states != 2 {
+            state.push_str(&format!(" / states {} {}", states, entry("tested").unwrap_or("1")));
+        }
+        let rule = Rule::from_state(dim_len, neighbourhood, &state)?;
+
+        if let (Some(expression), Condition::Tree(node)) = (entry("expression"), &rule.condition) {
+            let written = node.to_string();
+            if expression.split_whitespace().ne(written.split_whitespace()) {
+                return Err(format!("Expression {} doesn't match the condition {}", expression, written));
+            }
+        }
+        Ok(rule)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+        Rule::from_text(&text)
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = |input: usize| self.neighbourhood.input_name(self.dim_len, input, self.input_names);
+        match &self.condition {
+            Condition::Family(family, number) => match self.get_rulestring() {
+                Some(rulestring) => writeln!(f, "Rule: {} {} ({})", family.name(), number, rulestring)?,
+                None => writeln!(f, "Rule: {} {}", family.name(), number)?
+            },
+            _ => {
+                match &self.condition {
+                    Condition::Tree(node) if self.input_names == InputNames::Index => {
+                        writeln!(f, "Rule: {}", node)?;
+                        writeln!(f, "Permutation: {:?}", node.eval_permutation.get_vector())?;
+                    },
+                    Condition::Tree(node) => writeln!(f, "Rule: {}", node.to_expression().to_named_string(&name))?,
+                    Condition::Expression(expression) => writeln!(f, "Rule: {}", expression.to_named_string(&name))?,
+                    Condition::Family(..) => unreachable!()
+                }
+                writeln!(f, "Action: {:?}", self.result)?;
+                if let Some(else_result) = self.else_result {
+                    writeln!(f, "Else: {:?}", else_result)?;
+                }
+                if self.states > 2 {
+                    writeln!(f, "States: {}, the condition testing state {}", self.states, self.tested.state())?;
+                }
+                // Without the inputs the rule doesn't depend on, when its table is small enough
+                if self.print_minimal && self.neighbourhood.len(self.dim_len) <= MAX_NUMBERED_INPUTS {
+                    let minimal = self.minimise().unwrap();
+                    let reads: Vec<String> = minimal.inputs_read().into_iter().map(name).collect();
+                    let function = if self.states == 2 { "next value" } else { "condition" };
+                    writeln!(f, "Minimal {}: {}{}", function, minimal.to_named_string(&name), if minimal.is_minimal() { "" } else { " (not proven minimal)" })?;
+                    writeln!(f, "Reads: {}", reads.join(", "))?;
+                }
+            }
+        }
+
+        // Numbers of larger neighbourhoods are too long to be of use, and
+        // truth tables only have the next values of binary rules
+        if self.states == 2 && self.neighbourhood.len(self.dim_len) <= MAX_NUMBERED_INPUTS {
+            writeln!(f, "Number: {}", self.get_rule_number())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_truth_table() {
+        // Every candidate's next value matches its condition and actions
+        let mut rule = Rule::with_actions(1, Neighbourhood::Moore(1), Actions { unset: true, else_action: true });
+        loop {
+            let table = rule.get_truth_table();
+            for config in 0..table.len() {
+                let values = table.values(config);
+                let action = if rule.evaluate(&values) { Some(rule.result) } else { rule.else_result };
+                let expected = match action {
+                    None => values[0],
+                    Some(Action::Set) => true,
+                    Some(Action::Unset) => false,
+                    Some(Action::Flip) => !values[0],
+                    // Binary rules only have Set and Unset
+                    Some(Action::State(_)) => unreachable!()
+                };
+                assert_eq!(table.get(config), expected);
+            }
+
+            if !rule.has_next_candidate() {
+                break;
+            }
+            rule.generate_next_candidate();
+        }
+    }
+
+    #[test]
+    fn test_rule_number() {
+        // Inputs are the cell, right and left. The condition is left & centre & !right,
+        // flipping the centre off at 110 of left, centre, right, so it stays set at
+        // 010, 011 and 111
+        let rule = Rule::from_state(1, Neighbourhood::Moore(1), "Flip / & 0 v & 1 v ! / 2,0,1 0:0:0:0").unwrap();
+        assert_eq!(rule.get_rule_number(), (4 + 8 + 128).to_string());
+        assert!(rule.to_string().contains("Number: 140"));
+
+        // Too many inputs to print a number
+        assert!(!Rule::new(3).to_string().contains("Number: "));
+    }
+
+    #[test]
+    fn test_families() {
+        // Elementary rules are numbered the Wolfram way
+        let mut rule = Rule::with_family(1, Neighbourhood::Moore(1), Family::Elementary);
+        let mut count = 1;
+        while rule.has_next_candidate() {
+            assert_eq!(rule.get_rule_number(), rule.get_number().unwrap().to_string());
+            rule.generate_next_candidate();
+            count += 1;
+        }
+        assert_eq!(count, 256);
+        let rule = Rule::from_number(1, Neighbourhood::Moore(2), Family::Elementary, 1 << 31).unwrap();
+        assert_eq!(rule.get_rule_number(), (1_u64 << 31).to_string());
+
+        // Life: born with 3 neighbours, survives with 2 or 3
+        let life = Rule::from_number(2, Neighbourhood::Moore(1), Family::OuterTotalistic, 224).unwrap();
+        for set_neighbours in 0..9 {
+            assert_eq!(life.evaluate_outer_totalistic(false, set_neighbours), set_neighbours == 3);
+            assert_eq!(life.evaluate_outer_totalistic(true, set_neighbours), set_neighbours == 2 || set_neighbours == 3);
+        }
+
+        // Set when 1 or 2 of the 3 cells are
+        let rule = Rule::from_number(1, Neighbourhood::Moore(1), Family::Totalistic, 6).unwrap();
+        assert_eq!(rule.get_rule_number(), (2 + 4 + 8 + 16 + 32 + 64).to_string());
+        assert!(rule.evaluate(&vec![false, true, false]));
+        assert!(!rule.evaluate(&vec![true, true, false]));
+        assert!(rule.evaluate(&vec![true, true, true]));
+
+        for rule in [rule, life] {
+            let state = rule.get_state();
+            assert_eq!(Rule::from_state(rule.dim_len, rule.neighbourhood, &state).unwrap(), rule);
+        }
+        assert!(Rule::from_number(2, Neighbourhood::Moore(1), Family::Totalistic, 1 << 10).is_err());
+
+        assert!(Family::Elementary.supports(2, Neighbourhood::Moore(1)).is_err());
+        assert!(Family::Elementary.supports(1, Neighbourhood::Moore(4)).is_err());
+        assert_eq!(Family::Totalistic.rule_bits(3, Neighbourhood::Moore(1)), Ok(28));
+        assert_eq!(Family::OuterTotalistic.rule_bits(2, Neighbourhood::VonNeumann(1)), Ok(10));
+        assert!(Family::OuterTotalistic.supports(4, Neighbourhood::Moore(1)).is_err());
+        for family in [Family::Tree, Family::Elementary, Family::Totalistic, Family::OuterTotalistic] {
+            assert_eq!(Family::from_name(family.name()), Some(family));
+        }
+    }
+
+    #[test]
+    fn test_actions() {
+        assert_eq!(Actions::new().pairs(), vec![(Action::Set, None), (Action::Flip, None)]);
+        let all = Actions { unset: true, else_action: true };
+        assert_eq!(all.pairs().len(), 9);
+        assert!(all.pairs().contains(&(Action::Set, Some(Action::Unset))));
+        for actions in [Actions::new(), Actions { unset: true, else_action: false }, Actions { unset: false, else_action: true }, all] {
+            assert_eq!(Actions::from_name(&actions.name()), Some(actions));
+        }
+        assert_eq!(Actions::from_name("set,flip,else,unset"), None);
+        assert_eq!(Actions::from_name("flip"), None);
+
+        // Default actions keep the enumeration as it was
+        let mut rule = Rule::new(1);
+        let mut with_actions = Rule::with_actions(1, Neighbourhood::Moore(1), all);
+        let mut count = 1;
+        while with_actions.has_next_candidate() {
+            with_actions.generate_next_candidate();
+            let state = with_actions.get_state();
+            assert_eq!(Rule::from_state(1, Neighbourhood::Moore(1), &state).unwrap(), with_actions);
+            count += 1;
+        }
+        assert_eq!(count, 320);
+        assert!(with_actions.get_state().ends_with(" / set,flip,unset,else"));
+        count = 1;
+        while rule.has_next_candidate() {
+            rule.generate_next_candidate();
+            count += 1;
+        }
+        assert_eq!(count, 96);
+        assert!(!rule.get_state().contains("else"));
+
+        let rule = Rule::from_state(1, Neighbourhood::Moore(1), "Set else Unset / & 0 v & 1 v ! / 2,0,1 0:0:0:0 / set,flip,unset,else").unwrap();
+        assert_eq!(rule.else_result, Some(Action::Unset));
+        assert!(rule.to_string().contains("Else: Unset"));
+        assert!(Rule::from_state(1, Neighbourhood::Moore(1), "Set else Kill / & 0 v & 1 v ! / 2,0,1 0:0:0:0").is_err());
+    }
+
+    #[test]
+    fn test_rulestring() {
+        let life = Rule::from_rulestring(2, Neighbourhood::Moore(1), "B3/S23").unwrap();
+        assert_eq!(life.get_number(), Some(224));
+        assert_eq!(life.get_rulestring().unwrap(), "B3/S23");
+        assert!(life.to_string().contains("Rule: outer-totalistic 224 (B3/S23)"));
+
+        for rulestring in ["B36/S23", "B2/S", "B/S012345678", "B1357/S1357"] {
+            let rule = Rule::from_rulestring(2, Neighbourhood::Moore(1), rulestring).unwrap();
+            assert_eq!(rule.get_rulestring().unwrap(), rulestring);
+        }
+        assert_eq!(Rule::from_rulestring(2, Neighbourhood::Moore(1), "s32/b3").unwrap(), life);
+
+        // 26 neighbours in 3D
+        let rule = Rule::from_rulestring(3, Neighbourhood::Moore(1), "B5,6,7/S10,26").unwrap();
+        assert_eq!(rule.get_rulestring().unwrap(), "B5,6,7/S10,26");
+
+        for rulestring in ["B3", "B3/S23/S2", "3/23", "B9/S23", "Bx/S23", "B3/B23", "B3é/S23"] {
+            assert!(Rule::from_rulestring(2, Neighbourhood::Moore(1), rulestring).is_err());
+        }
+        assert!(Rule::from_rulestring(4, Neighbourhood::Moore(1), "B3/S23").is_err());
+        assert_eq!(Rule::new(2).get_rulestring(), None);
+    }
+
+    #[test]
+    fn test_text() {
+        // Every candidate of the enumeration reads back as itself
+        for (states, actions) in [(2, Actions::new()), (2, Actions { unset: true, else_action: true }), (3, Actions { unset: false, else_action: true })] {
+            let mut rule = Rule::with_actions(1, Neighbourhood::Moore(1), actions).with_states(states);
+            loop {
+                assert_eq!(Rule::from_text(&rule.to_text()).unwrap(), rule);
+                if !rule.has_next_candidate() {
+                    break;
+                }
+                rule.generate_next_candidate();
+            }
+        }
+        for rule in [
+            Rule::from_rulestring(2, Neighbourhood::Moore(1), "B3/S23").unwrap(),
+            Rule::from_rulestring(2, Neighbourhood::Moore(1), "B2/S/3").unwrap(),
+            Rule::from_number(3, Neighbourhood::VonNeumann(2), Family::Totalistic, 77).unwrap(),
+            Rule::with_neighbourhood(2, Neighbourhood::VonNeumann(1))
+        ] {
+            assert_eq!(Rule::from_text(&rule.to_text()).unwrap(), rule);
+        }
+
+        let text = "rule 1
+            dimension 1
+            neighbourhood moore
+            family tree
+            states 2
+            condition & 0 v & 1 v v
+            permutation 0,1,2
+            tested 1
+            action Flip
+            actions set,flip
+            expression ( 0 & ( 1 & 2 ) )";
+        let rule = Rule::from_text(text).unwrap();
+        assert_eq!(rule.result, Action::Flip);
+        assert_eq!(rule.get_rule_number(), "76");
+        assert_eq!(rule.to_text().lines().collect::<Vec<&str>>(), text.lines().map(|line| line.trim()).collect::<Vec<&str>>());
+        assert_eq!(Rule::from_text("rule 1\ndimension 2\nneighbourhood moore\nfamily outer-totalistic\nnumber 224").unwrap().get_rulestring().unwrap(), "B3/S23");
+
+        // The expression has to be the condition, and optional entries default
+        let changed = text.replace("expression ( 0 & ( 1 & 2 ) )", "expression ( 0 & ( 1 | 2 ) )");
+        assert!(Rule::from_text(&changed).is_err());
+        let without: String = text.lines().filter(|line| !line.contains("expression") && !line.contains("actions") && !line.contains("states")).collect::<Vec<&str>>().join("\n");
+        assert_eq!(Rule::from_text(&without).unwrap(), rule);
+
+        for (entry, replacement) in [("rule 1", "rule 2"), ("dimension 1", "dimension 0"), ("neighbourhood moore", "neighbourhood hex"),
+            ("family tree", "family elementary"), ("action Flip", "action Toggle"), ("tested 1", "colour 1"), ("action Flip", "action Flip\naction Set"),
+            ("states 2", "states 1"), ("condition & 0 v & 1 v v", "condition & 0 v")] {
+            assert!(Rule::from_text(&text.replace(entry, replacement)).is_err(), "{}", replacement);
+        }
+        assert!(Rule::from_text(&text.replace("action Flip\n", "")).is_err());
+    }
+
+    #[test]
+    fn test_expression() {
+        // Set where the cell above is set and the one below isn't, unset elsewhere
+        let neighbourhood = Neighbourhood::Moore(1);
+        let rule = Rule::from_expression(2, neighbourhood, "N & !S", Action::Set, Some(Action::Unset)).unwrap();
+        let (north, south) = (neighbourhood.input_index(2, &[-1, 0]).unwrap(), neighbourhood.input_index(2, &[1, 0]).unwrap());
+        let table = rule.get_truth_table();
+        for config in 0..table.len() {
+            let values = table.values(config);
+            assert_eq!(table.get(config), values[north] && !values[south]);
+        }
+        assert_eq!((rule.get_family(), rule.get_number(), rule.has_next()), (Family::Tree, None, false));
+        assert!(rule.to_string().starts_with(&format!("Rule: ( {} & !{} )", north, south)));
+
+        // It reads back from its state and text, with more states too
+        for rule in [rule.clone(), rule.clone().with_states(3), Rule::from_expression(1, neighbourhood, "(-1) | 0", Action::Flip, None).unwrap()] {
+            assert_eq!(Rule::from_state(rule.get_dimension(), neighbourhood, &rule.get_state()).unwrap(), rule);
+            assert_eq!(Rule::from_text(&rule.to_text()).unwrap(), rule);
+        }
+        assert_eq!(rule.get_state(), format!("Set else Unset / expression ( {} & !{} )", north, south));
+        assert!(!rule.to_text().contains("condition"));
+
+        // Cells in other states than the one tested count as unset
+        let rule = rule.with_states(3);
+        let mut cells = vec![CellValue::Unset; 9];
+        cells[north] = CellValue::Set;
+        assert_eq!(rule.next_state(&cells), CellValue::Set);
+        cells[south] = CellValue::new(2);
+        assert_eq!(rule.next_state(&cells), CellValue::Set);
+        cells[south] = CellValue::Set;
+        assert_eq!(rule.next_state(&cells), CellValue::Unset);
+
+        assert!(Rule::from_expression(2, neighbourhood, "N &", Action::Set, None).is_err());
+        assert!(Rule::from_state(2, neighbourhood, "Set / expression NE / set,flip / extra").is_err());
+    }
+
+    #[test]
+    fn test_input_names() {
+        // A rule printed with named inputs reads back as a condition with the same truth table
+        let neighbourhood = Neighbourhood::VonNeumann(1);
+        let mut rule = Rule::with_neighbourhood(2, neighbourhood);
+        for _ in 0..200 {
+            rule.generate_next_candidate();
+        }
+        for input_names in [InputNames::Offset, InputNames::Compass] {
+            let printed = rule.clone().with_input_names(input_names).to_string();
+            assert!(!printed.contains("Permutation"));
+            let condition = printed.lines().next().unwrap().strip_prefix("Rule: ").unwrap();
+            let parsed = Rule::from_expression(2, neighbourhood, condition, rule.result, rule.else_result).unwrap();
+            assert_eq!(parsed.get_truth_table(), rule.get_truth_table());
+        }
+        assert_eq!(rule.clone().with_input_names(InputNames::Index).to_string(), rule.to_string());
+
+        let rule = Rule::from_expression(2, Neighbourhood::Moore(1), "N & !(+1,+1)", Action::Set, None).unwrap();
+        assert!(rule.clone().with_input_names(InputNames::Compass).to_string().starts_with("Rule: ( N & !SE )"));
+        assert!(rule.with_input_names(InputNames::Offset).to_string().starts_with("Rule: ( (-1,0) & !(+1,+1) )"));
+    }
+
+    #[test]
+    fn test_minimise() {
+        // Setting cells where the cell is set and more leaves every cell as it is
+        let rule = Rule::new(1).with_minimal(true);
+        assert_eq!(rule.minimise().unwrap().to_string(), "0");
+        assert!(!Rule::new(1).to_string().contains("Minimal"));
+        assert!(rule.to_string().contains("Minimal next value: 0\nReads: 0\n"));
+
+        // Only the inputs the next value depends on
+        let rule = Rule::from_expression(2, Neighbourhood::Moore(1), "N & (S | !S) | N & W", Action::Set, Some(Action::Unset)).unwrap()
+            .with_input_names(InputNames::Compass).with_minimal(true);
+        assert_eq!(rule.minimise().unwrap().inputs_read(), vec![Neighbourhood::Moore(1).input_index(2, &[-1, 0]).unwrap()]);
+        assert!(rule.to_string().contains("Minimal next value: N\nReads: N\n"));
+
+        // The condition for rules of more states, nothing for other families
+        assert!(rule.with_states(3).to_string().contains("Minimal condition: N\n"));
+        assert_eq!(Rule::from_rulestring(2, Neighbourhood::Moore(1), "B3/S23").unwrap().minimise(), None);
+        assert!(!Rule::new(3).with_minimal(true).to_string().contains("Minimal"));
+    }
+
+    #[test]
+    fn test_states() {
+        let state = CellValue::new;
+
+        // Brian's Brain: born with 2 set neighbours, set cells never survive
+        let brain = Rule::from_rulestring(2, Neighbourhood::Moore(1), "B2/S/3").unwrap();
+        assert_eq!(brain.get_family(), Family::Generations);
+        assert_eq!(brain.get_states(), 3);
+        assert_eq!(brain.get_rulestring().unwrap(), "B2/S/3");
+        assert_eq!(Rule::from_rulestring(2, Neighbourhood::Moore(1), "b2/s/c3").unwrap(), brain);
+        assert_eq!(Rule::from_state(2, Neighbourhood::Moore(1), &brain.get_state()).unwrap(), brain);
+        assert_eq!(brain.get_state(), "generations 16 states 3");
+
+        let mut v = [CellValue::Unset; 9];
+        (v[1], v[2], v[3]) = (CellValue::Set, CellValue::Set, state(2));
+        assert_eq!(brain.next_state(&v), CellValue::Set);
+        v[0] = CellValue::Set;
+        assert_eq!(brain.next_state(&v), state(2));
+        v[0] = state(2);
+        assert_eq!(brain.next_state(&v), CellValue::Unset);
+        assert!(brain.acts(&v));
+
+        // With 2 states, generations rules are outer-totalistic
+        let life = Rule::from_rulestring(2, Neighbourhood::Moore(1), "B3/S23/2").unwrap();
+        assert_eq!(life.get_truth_table(), Rule::from_rulestring(2, Neighbourhood::Moore(1), "B3/S23").unwrap().get_truth_table());
+        for rulestring in ["B2/S/1", "B2/S/x", "B2/S/3/4"] {
+            assert!(Rule::from_rulestring(2, Neighbourhood::Moore(1), rulestring).is_err());
+        }
+
+        // Tree conditions test a state, and actions move cells to any state
+        let mut rule = Rule::with_actions(1, Neighbourhood::Moore(1), Actions { unset: false, else_action: true }).with_states(3);
+        let mut tested = vec![];
+        loop {
+            assert_eq!(Rule::from_state(1, Neighbourhood::Moore(1), &rule.get_state()).unwrap(), rule);
+            tested.push(rule.tested);
+
+            let v = [state(2), state(2), CellValue::Unset];
+            let condition = rule.get_condition_truth_table().evaluate(&v.map(|el| el == rule.tested));
+            let action = if condition { Some(rule.result) } else { rule.else_result };
+            let expected = match action {
+                None => v[0],
+                Some(Action::Set) => CellValue::Set,
+                Some(Action::Unset) => CellValue::Unset,
+                Some(Action::Flip) => CellValue::Unset,
+                Some(Action::State(s)) => state(s as usize)
+            };
+            assert_eq!(rule.next_state(&v), expected);
+
+            if !rule.has_next_candidate() {
+                break;
+            }
+            rule.generate_next_candidate();
+        }
+        assert!(tested.contains(&state(2)));
+        assert!(Rule::from_state(1, Neighbourhood::Moore(1), "State(3) / & 0 v & 1 v v / 0,1,2 / set,flip / states 3 1").is_err());
+
+        assert!(Family::Generations.supports_states(256).is_ok());
+        assert!(Family::Tree.supports_states(1).is_err());
+        assert!(Family::OuterTotalistic.supports_states(3).is_err());
+    }
+}
@@ -0,0 +1,388 @@
+/*
+* Rule sets
+*
+* Several rules over the same neighbourhood updating a cell together. A rule
+* acts on a cell when its condition holds, or everywhere when it has an else
+* action, and the policy decides what the rules acting on a cell do:
+* - first-match: the first rule acting gives the next value, as a list of
+*   rules in priority order
+* - last-match: the last rule acting gives the next value
+* - majority: the cell takes the next state most rules give, a rule not acting
+*   giving the current state. Ties leave the cell unchanged
+*
+* A set of k rules is enumerated as k positions of the rule enumeration, the
+* last rule changing fastest. A set of one rule is that rule, whatever the policy.
+*/
+
+use std::fmt;
+
+use crate::cell::CellValue;
+use crate::neighbourhood::{InputNames, Neighbourhood};
+use crate::rule::Rule;
+
+// Separates the policy and the rule states in the state of a set
+const STATE_SEPARATOR: &str = " + ";
+
+// Neighbourhoods with more configurations than this, such as the 3^9 of the 2D
+// Moore neighbourhood with 3 states, take longer to fingerprint than to simulate
+const MAX_FINGERPRINT_CONFIGS: usize = 1 << 12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Policy {
+    FirstMatch,
+    LastMatch,
+    Majority
+}
+
+impl Policy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Policy::FirstMatch => "first-match",
+            Policy::LastMatch => "last-match",
+            Policy::Majority => "majority"
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Policy> {
+        match name {
+            "first-match" => Some(Policy::FirstMatch),
+            "last-match" => Some(Policy::LastMatch),
+            "majority" => Some(Policy::Majority),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct RuleSet {
+    rules: Vec<Rule>,
+    policy: Policy
+}
+
+impl RuleSet {
+    pub fn new(rules: Vec<Rule>, policy: Policy) -> Result<Self, String> {
+        let first = rules.first().ok_or("A rule set has at least one rule".to_string())?;
+        if rules.iter().any(|rule| rule.get_neighbourhood() != first.get_neighbourhood() || rule.get_dimension() != first.get_dimension()) {
+            return Err("Rules of a set have to read the same neighbourhood".to_string());
+        }
+
+        Ok(RuleSet {
+            rules,
+            policy
+        })
+    }
+
+    pub fn from_rule(rule: &Rule) -> Self {
+        RuleSet {
+            rules: vec![rule.clone()],
+            policy: Policy::FirstMatch
+        }
+    }
+
+    // First set of the enumeration, every rule being the given first rule
+    pub fn first(len: usize, policy: Policy, rule: Rule) -> Self {
+        RuleSet {
+            rules: vec![rule; len.max(1)],
+            policy
+        }
+    }
+
+    // Prints the rules with the inputs named, see Rule::with_input_names
+    pub fn with_input_names(mut self, input_names: InputNames) -> Self {
+        self.rules = self.rules.into_iter().map(|rule| rule.with_input_names(input_names)).collect();
+        self
+    }
+
+    // Prints the minimal sums of products of the rules, see Rule::with_minimal
+    pub fn with_minimal(mut self, print_minimal: bool) -> Self {
+        self.rules = self.rules.into_iter().map(|rule| rule.with_minimal(print_minimal)).collect();
+        self
+    }
+
+    pub fn get_rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn get_policy(&self) -> Policy {
+        self.policy
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn get_neighbourhood(&self) -> Neighbourhood {
+        self.rules[0].get_neighbourhood()
+    }
+
+    // Value of the cell in the next generation, for binary rules. The cell itself is v[0]
+    pub fn next_value(&self, v: &[bool]) -> bool {
+        let states: Vec<CellValue> = v.iter().map(|el| CellValue::from(*el)).collect();
+        self.next_state(&states).is_set()
+    }
+
+    // State of the cell in the next generation
+    pub fn next_state(&self, v: &[CellValue]) -> CellValue {
+        if let [rule] = self.rules.as_slice() {
+            return rule.next_state(v);
+        }
+
+        match self.policy {
+            Policy::FirstMatch => self.rules.iter().find(|rule| rule.acts(v))
+                .map_or(v[0], |rule| rule.next_state(v)),
+            Policy::LastMatch => self.rules.iter().rev().find(|rule| rule.acts(v))
+                .map_or(v[0], |rule| rule.next_state(v)),
+            Policy::Majority => {
+                let next: Vec<CellValue> = self.rules.iter().map(|rule| rule.next_state(v)).collect();
+                let votes = |state: &CellValue| next.iter().filter(|el| *el == state).count();
+                let most = next.iter().map(votes).max().unwrap();
+                let mut winners: Vec<CellValue> = next.iter().copied().filter(|state| votes(state) == most).collect();
+                winners.sort_by_key(|state| state.state());
+                winners.dedup();
+                if winners.len() == 1 { winners[0] } else { v[0] }
+            }
+        }
+    }
+
+    // Next state of every configuration of the neighbourhood, the same for sets computing
+    // the same function whatever their rules. Configuration c has input i in state
+    // (c / states^i) % states, and binary states are packed 8 to a byte. None when the
+    // neighbourhood has more than MAX_FINGERPRINT_CONFIGS configurations
+    pub fn fingerprint(&self) -> Option<Vec<u8>> {
+        let states = self.rules.iter().map(|rule| rule.get_states()).max().unwrap();
+        let inputs = self.get_neighbourhood().len(self.rules[0].get_dimension());
+        let configs = u32::try_from(inputs).ok()
+            .and_then(|inputs| states.checked_pow(inputs))
+            .filter(|configs| *configs <= MAX_FINGERPRINT_CONFIGS)?;
+        // Single binary rules evaluate every configuration at once
+        if let ([rule], 2) = (self.rules.as_slice(), states) {
+            return Some(rule.get_truth_table().to_bytes());
+        }
+
+        let mut v = vec![CellValue::Unset; inputs];
+        let next = (0..configs).map(|config| {
+            let mut rest = config;
+            for el in v.iter_mut() {
+                *el = CellValue::new(rest % states);
+                rest /= states;
+            }
+            self.next_state(&v).state() as u8
+        });
+
+        if states == 2 {
+            let mut bytes = vec![0; configs.div_ceil(8)];
+            for (config, state) in next.enumerate() {
+                bytes[config / 8] |= state << (config % 8);
+            }
+            Some(bytes)
+        }
+        else {
+            Some(next.collect())
+        }
+    }
+
+    pub fn print(&self) {
+        print!("{}", self);
+    }
+
+    pub fn debug_print(&self) {
+        for rule in self.rules.iter() {
+            rule.debug_print();
+        }
+    }
+
+    // The enumeration moves to another rule of the set, rather than to another
+    // evaluation permutation of the last rule
+    pub fn has_next_eval_permutation(&self) -> bool {
+        self.rules[self.rules.len() - 1].has_next_eval_permutation()
+    }
+
+    pub fn has_next_candidate(&self) -> bool {
+        self.rules.iter().any(|rule| rule.has_next_candidate())
+    }
+
+    pub fn generate_next_candidate(&mut self) {
+        // Rules past the last one with a next candidate start over
+        for rule in self.rules.iter_mut().rev() {
+            if rule.has_next_candidate() {
+                rule.generate_next_candidate();
+                return;
+            }
+            *rule = Rule::first(rule.get_dimension(), rule.get_neighbourhood(), rule.get_family(), rule.get_actions())
+                .with_states(rule.get_states());
+        }
+    }
+
+    // The state of the rule for a set of one rule under first-match, otherwise
+    // the policy then the state of every rule, separated by " + "
+    pub fn get_state(&self) -> String {
+        if self.rules.len() == 1 && self.policy == Policy::FirstMatch {
+            return self.rules[0].get_state();
+        }
+
+        let mut parts = vec![self.policy.name().to_string()];
+        parts.extend(self.rules.iter().map(|rule| rule.get_state()));
+        parts.join(STATE_SEPARATOR)
+    }
+
+    pub fn from_state(dim_len: usize, neighbourhood: Neighbourhood, state: &str) -> Result<Self, String> {
+        let parts: Vec<&str> = state.split(STATE_SEPARATOR).collect();
+        if parts.len() == 1 {
+            return Ok(RuleSet::from_rule(&Rule::from_state(dim_len, neighbourhood, state)?));
+        }
+
+        let policy = Policy::from_name(parts[0].trim()).ok_or(format!("Unknown rule set policy {}", parts[0].trim()))?;
+        let rules = parts[1..].iter().map(|part| Rule::from_state(dim_len, neighbourhood, part)).collect::<Result<Vec<Rule>, String>>()?;
+        RuleSet::new(rules, policy)
+    }
+}
+
+impl fmt::Display for RuleSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let [rule] = self.rules.as_slice() {
+            return write!(f, "{}", rule);
+        }
+
+        writeln!(f, "Rule set of {} rules, {}", self.rules.len(), self.policy.name())?;
+        for (i, rule) in self.rules.iter().enumerate() {
+            writeln!(f, "#{}", i + 1)?;
+            write!(f, "{}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rule::{Action, Actions, Family};
+
+    fn life_like(rulestring: &str) -> Rule {
+        Rule::from_rulestring(2, Neighbourhood::Moore(1), rulestring).unwrap()
+    }
+
+    #[test]
+    fn test_policies() {
+        // Birth on 3 neighbours, and death on anything but 2 or 3
+        let birth = life_like("B3/S012345678");
+        let death = life_like("B/S23");
+        let configs = |set: bool, neighbours: usize| {
+            let mut v = vec![false; 9];
+            v[0] = set;
+            for el in v.iter_mut().skip(1).take(neighbours) {
+                *el = true;
+            }
+            v
+        };
+
+        // Each rule only acts where the cell changes, so together they are Life
+        let life = life_like("B3/S23");
+        for policy in [Policy::FirstMatch, Policy::LastMatch] {
+            let rule_set = RuleSet::new(vec![birth.clone(), death.clone()], policy).unwrap();
+            for set in [false, true] {
+                for neighbours in 0..9 {
+                    let v = configs(set, neighbours);
+                    assert_eq!(rule_set.next_value(&v), life.next_value(&v));
+                }
+            }
+        }
+
+        // Rules with an else action act everywhere, so the order decides.
+        // Over the same condition, these two always disagree
+        let actions = Actions { unset: true, else_action: true };
+        let rule_with = |result: Action, else_result: Action| {
+            let mut rule = Rule::with_actions(1, Neighbourhood::Moore(1), actions);
+            while (rule.result, rule.else_result) != (result, Some(else_result)) {
+                rule.generate_next_candidate();
+            }
+            rule
+        };
+        let set = rule_with(Action::Set, Action::Unset);
+        let unset = rule_with(Action::Unset, Action::Set);
+        for config in 0..8 {
+            let v: Vec<bool> = (0..3).map(|i| config >> i & 1 == 1).collect();
+            assert_eq!(RuleSet::new(vec![set.clone(), unset.clone()], Policy::FirstMatch).unwrap().next_value(&v), set.next_value(&v));
+            assert_eq!(RuleSet::new(vec![set.clone(), unset.clone()], Policy::LastMatch).unwrap().next_value(&v), unset.next_value(&v));
+        }
+
+        // Majority of three, and a tie leaving the cell unchanged
+        let all = Rule::from_number(1, Neighbourhood::Moore(1), Family::Totalistic, 0b1111).unwrap();
+        let none = Rule::from_number(1, Neighbourhood::Moore(1), Family::Totalistic, 0).unwrap();
+        let majority = RuleSet::new(vec![all.clone(), none.clone(), all.clone()], Policy::Majority).unwrap();
+        assert!(majority.next_value(&[false, false, false]));
+        let tie = RuleSet::new(vec![all, none], Policy::Majority).unwrap();
+        assert!(!tie.next_value(&[false, true, false]));
+        assert!(tie.next_value(&[true, true, false]));
+
+        assert!(RuleSet::new(vec![], Policy::Majority).is_err());
+        assert!(RuleSet::new(vec![Rule::new(1), Rule::new(2)], Policy::Majority).is_err());
+    }
+
+    #[test]
+    fn test_enumeration() {
+        // Every pair of candidates, the last rule changing fastest
+        let rule = Rule::first(1, Neighbourhood::VonNeumann(1), Family::Tree, Actions::new());
+        let mut candidates = 1;
+        let mut single = rule.clone();
+        while single.has_next_candidate() {
+            single.generate_next_candidate();
+            candidates += 1;
+        }
+
+        let mut rule_set = RuleSet::first(2, Policy::Majority, rule.clone());
+        let mut count = 1;
+        while rule_set.has_next_candidate() {
+            rule_set.generate_next_candidate();
+            count += 1;
+            if count == candidates + 1 {
+                assert_eq!(rule_set.get_rules()[1], rule);
+                assert_ne!(rule_set.get_rules()[0], rule);
+            }
+        }
+        assert_eq!(count, candidates * candidates);
+        assert_eq!(rule_set.get_rules(), [single.clone(), single]);
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        // Elementary rules are the 256 functions of 3 inputs, each with its own fingerprint
+        let mut fingerprints = vec![];
+        for number in 0..256 {
+            let rule = Rule::from_number(1, Neighbourhood::Moore(1), Family::Elementary, number).unwrap();
+            let fingerprint = RuleSet::from_rule(&rule).fingerprint().unwrap();
+            assert_eq!(fingerprint.len(), 1);
+            fingerprints.push(fingerprint);
+        }
+        fingerprints.sort_unstable();
+        fingerprints.dedup();
+        assert_eq!(fingerprints.len(), 256);
+
+        // A set of the same rule twice computes that rule
+        let life = life_like("B3/S23");
+        let twice = RuleSet::new(vec![life.clone(), life.clone()], Policy::LastMatch).unwrap();
+        assert_eq!(twice.fingerprint(), RuleSet::from_rule(&life).fingerprint());
+        assert_eq!(twice.fingerprint().unwrap().len(), 64);
+        assert_ne!(twice.fingerprint(), RuleSet::from_rule(&life_like("B36/S23")).fingerprint());
+
+        // One byte per configuration for more states, none past MAX_FINGERPRINT_CONFIGS
+        let ternary = Rule::new(1).with_states(3);
+        assert_eq!(RuleSet::from_rule(&ternary).fingerprint().unwrap().len(), 27);
+        assert_eq!(RuleSet::from_rule(&Rule::new(3)).fingerprint(), None);
+    }
+
+    #[test]
+    fn test_state() {
+        let rule = Rule::first(2, Neighbourhood::Moore(1), Family::OuterTotalistic, Actions::new());
+        let rule_set = RuleSet::from_rule(&rule);
+        assert_eq!(rule_set.get_state(), rule.get_state());
+        assert_eq!(RuleSet::from_state(2, Neighbourhood::Moore(1), &rule_set.get_state()).unwrap(), rule_set);
+
+        let mut rule_set = RuleSet::first(3, Policy::LastMatch, Rule::new(1));
+        for _ in 0..100 {
+            rule_set.generate_next_candidate();
+        }
+        assert_eq!(rule_set.get_state().split(" + ").next(), Some("last-match"));
+        assert_eq!(RuleSet::from_state(1, Neighbourhood::Moore(1), &rule_set.get_state()).unwrap(), rule_set);
+        assert!(RuleSet::from_state(1, Neighbourhood::Moore(1), "any-match + elementary 30").is_err());
+    }
+}
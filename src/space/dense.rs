@@ -0,0 +1,294 @@
+/*
+* Dense cell store
+*
+* A box of the space as an N-dimensional bit array, the first coordinate
+* changing fastest. Each cell of the box takes a slot of a few bits: whether
+* the cell is in the space in the lowest bit, then its state. Slots are 2 bits
+* for binary rules and widen when a cell is given a state they can't hold, a
+* power of two bits so that no slot spans two words.
+*
+* The box grows when a cell is added outside it, by half its length on that side,
+* so the neighbours the space adds around set cells on the edge rarely move it again.
+* The slots of the cells are listed in the order the cells were added, as with the
+* sparse store, so going through the cells never scans the empty slots of the box.
+*/
+
+use crate::cell::CellValue;
+use crate::space::CellStore;
+
+// Cells added around a cell outside the box, at the least
+const MARGIN: usize = 2;
+
+#[derive(Debug, Clone)]
+pub struct DenseStore {
+    // Lowest coordinates of the box, and its length along each axis
+    origin: Vec<i32>,
+    shape: Vec<usize>,
+    strides: Vec<usize>,
+    slot_bits: usize,
+    words: Vec<u64>,
+    // Slots of the cells in the space, in the order they were added
+    occupied: Vec<usize>
+}
+
+impl DenseStore {
+    pub fn new(dim_len: usize) -> Self {
+        DenseStore {
+            origin: vec![0; dim_len],
+            shape: vec![0; dim_len],
+            strides: vec![0; dim_len],
+            slot_bits: 2,
+            words: vec![],
+            occupied: vec![]
+        }
+    }
+
+    fn index(&self, coordinates: &[i32]) -> Option<usize> {
+        let mut index = 0;
+        for (i, coordinate) in coordinates.iter().enumerate() {
+            let position = coordinate - self.origin[i];
+            if position < 0 || position as usize >= self.shape[i] {
+                return None;
+            }
+            index += position as usize * self.strides[i];
+        }
+        Some(index)
+    }
+
+    fn coordinates(&self, mut index: usize) -> Vec<i32> {
+        self.shape.iter().zip(self.origin.iter()).map(|(len, origin)| {
+            let position = index % len;
+            index /= len;
+            origin + position as i32
+        }).collect()
+    }
+
+    fn slot(&self, index: usize) -> u64 {
+        let bit = index * self.slot_bits;
+        (self.words[bit / 64] >> (bit % 64)) & ((1 << self.slot_bits) - 1)
+    }
+
+    fn set_slot(&mut self, index: usize, slot: u64) {
+        let bit = index * self.slot_bits;
+        let mask = ((1 << self.slot_bits) - 1) << (bit % 64);
+        self.words[bit / 64] = (self.words[bit / 64] & !mask) | (slot << (bit % 64));
+    }
+
+    fn value(slot: u64) -> CellValue {
+        CellValue::new((slot >> 1) as usize)
+    }
+
+    // Moves the cells to a box of another origin and shape, or to slots of another length
+    fn repack(&mut self, origin: Vec<i32>, shape: Vec<usize>, slot_bits: usize) {
+        let cells: Vec<(Vec<i32>, CellValue)> = self.cells().collect();
+
+        let mut strides = vec![1; shape.len()];
+        for i in 1..shape.len() {
+            strides[i] = strides[i - 1] * shape[i - 1];
+        }
+        let capacity: usize = shape.iter().product();
+        *self = DenseStore {
+            origin,
+            shape,
+            strides,
+            slot_bits,
+            words: vec![0; (capacity * slot_bits).div_ceil(64)],
+            occupied: Vec::with_capacity(cells.len())
+        };
+
+        for (coordinates, value) in cells {
+            self.insert(&coordinates, value);
+        }
+    }
+
+    // Grows the box to hold the cell, with a margin on the sides it grows
+    fn grow(&mut self, coordinates: &[i32]) {
+        let mut origin = self.origin.clone();
+        let mut shape = self.shape.clone();
+        for (i, coordinate) in coordinates.iter().enumerate() {
+            let margin = (self.shape[i] / 2).max(MARGIN) as i32;
+            let (mut start, mut end) = (self.origin[i], self.origin[i] + self.shape[i] as i32);
+            if self.occupied.is_empty() {
+                (start, end) = (coordinate - margin, coordinate + margin + 1);
+            }
+            else if *coordinate < start {
+                start = coordinate - margin;
+            }
+            else if *coordinate >= end {
+                end = coordinate + margin + 1;
+            }
+            origin[i] = start;
+            shape[i] = (end - start) as usize;
+        }
+        self.repack(origin, shape, self.slot_bits);
+    }
+}
+
+impl CellStore for DenseStore {
+    fn get(&self, coordinates: &[i32]) -> Option<CellValue> {
+        let slot = self.slot(self.index(coordinates)?);
+        (slot & 1 == 1).then(|| DenseStore::value(slot))
+    }
+
+    fn insert(&mut self, coordinates: &[i32], value: CellValue) {
+        // Bits of the state and of being in the space
+        let bits = (usize::BITS - value.state().leading_zeros()) as usize + 1;
+        if bits > self.slot_bits {
+            self.repack(self.origin.clone(), self.shape.clone(), bits.next_power_of_two());
+        }
+
+        let index = match self.index(coordinates) {
+            Some(index) => index,
+            None => {
+                self.grow(coordinates);
+                self.index(coordinates).unwrap()
+            }
+        };
+        if self.slot(index) & 1 == 0 {
+            self.occupied.push(index);
+        }
+        self.set_slot(index, (value.state() as u64) << 1 | 1);
+    }
+
+    fn len(&self) -> usize {
+        self.occupied.len()
+    }
+
+    // The box keeps its shape
+    fn pop(&mut self) -> Option<(Vec<i32>, CellValue)> {
+        let index = self.occupied.pop()?;
+        let value = DenseStore::value(self.slot(index));
+        self.set_slot(index, 0);
+        Some((self.coordinates(index), value))
+    }
+
+    fn cells(&self) -> Box<dyn Iterator<Item = (Vec<i32>, CellValue)> + '_> {
+        Box::new(self.occupied.iter().map(|index| (self.coordinates(*index), DenseStore::value(self.slot(*index)))))
+    }
+
+    // The coordinates are kept from one cell to the next
+    fn for_each_cell(&self, f: &mut dyn FnMut(&[i32], CellValue)) {
+        let mut coordinates = vec![0; self.shape.len()];
+        for index in self.occupied.iter() {
+            let mut position = *index;
+            for (i, coordinate) in coordinates.iter_mut().enumerate() {
+                *coordinate = self.origin[i] + (position % self.shape[i]) as i32;
+                position /= self.shape[i];
+            }
+            f(&coordinates, DenseStore::value(self.slot(*index)));
+        }
+    }
+
+    fn values(&self) -> Box<dyn Iterator<Item = CellValue> + '_> {
+        Box::new(self.occupied.iter().map(|index| DenseStore::value(self.slot(*index))))
+    }
+
+    fn set_values(&mut self, values: &[CellValue]) {
+        for (i, value) in values.iter().enumerate().take(self.occupied.len()) {
+            let index = self.occupied[i];
+            if value.state() as u64 >= 1 << (self.slot_bits - 1) {
+                // Widens the slots, which doesn't move the cells
+                self.insert(&self.coordinates(index), *value);
+            }
+            else {
+                self.set_slot(index, (value.state() as u64) << 1 | 1);
+            }
+        }
+    }
+
+    // The hot path of the search: no coordinates are built, each
+    // neighbour is found by its offset in the array
+    fn neighbourhood_states(&self, coordinates: &[i32], inputs: &[Vec<i32>]) -> Vec<CellValue> {
+        inputs.iter().map(|offset| {
+            let mut index = 0;
+            for i in 0..coordinates.len() {
+                let position = coordinates[i] + offset[i] - self.origin[i];
+                if position < 0 || position as usize >= self.shape[i] {
+                    return CellValue::Unset;
+                }
+                index += position as usize * self.strides[i];
+            }
+            DenseStore::value(self.slot(index))
+        }).collect()
+    }
+
+    fn heap_bytes(&self) -> usize {
+        self.words.capacity() * size_of::<u64>() + self.occupied.capacity() * size_of::<usize>()
+            + self.origin.capacity() * size_of::<i32>() + (self.shape.capacity() + self.strides.capacity()) * size_of::<usize>()
+    }
+
+    fn clone_box(&self) -> Box<dyn CellStore> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_grow() {
+        let mut store = DenseStore::new(2);
+        assert_eq!(store.get(&[0, 0]), None);
+
+        store.insert(&[0, 0], CellValue::Set);
+        store.insert(&[1, 0], CellValue::Unset);
+        assert_eq!(store.len(), 2);
+        assert_eq!(store.get(&[0, 0]), Some(CellValue::Set));
+        assert_eq!(store.get(&[1, 0]), Some(CellValue::Unset));
+        assert_eq!(store.get(&[0, 1]), None);
+
+        // Far outside the box, on both sides
+        store.insert(&[-40, 7], CellValue::Set);
+        store.insert(&[3, 100], CellValue::Set);
+        store.insert(&[0, 0], CellValue::Unset);
+        assert_eq!(store.len(), 4);
+        assert_eq!(store.get(&[-40, 7]), Some(CellValue::Set));
+        assert_eq!(store.get(&[3, 100]), Some(CellValue::Set));
+        assert_eq!(store.get(&[0, 0]), Some(CellValue::Unset));
+        assert_eq!(store.get(&[1, 0]), Some(CellValue::Unset));
+        assert_eq!(store.get(&[-39, 7]), None);
+
+        let mut cells: Vec<(Vec<i32>, CellValue)> = store.cells().collect();
+        cells.sort_by_key(|(coordinates, _)| coordinates.clone());
+        assert_eq!(cells, vec![
+            (vec![-40, 7], CellValue::Set),
+            (vec![0, 0], CellValue::Unset),
+            (vec![1, 0], CellValue::Unset),
+            (vec![3, 100], CellValue::Set)
+        ]);
+    }
+
+    #[test]
+    fn test_states() {
+        // Slots widen for states they can't hold, keeping the other cells
+        let mut store = DenseStore::new(1);
+        for i in 0..10 {
+            store.insert(&[i], CellValue::from(i % 2 == 0));
+        }
+        store.insert(&[3], CellValue::new(2));
+        store.insert(&[-3], CellValue::new(255));
+        for i in 0..10 {
+            let expected = if i == 3 { CellValue::new(2) } else { CellValue::from(i % 2 == 0) };
+            assert_eq!(store.get(&[i]), Some(expected));
+        }
+        assert_eq!(store.get(&[-3]), Some(CellValue::new(255)));
+
+        let values: Vec<CellValue> = (0..store.len()).map(|i| CellValue::new(i * 20)).collect();
+        store.set_values(&values);
+        assert_eq!(store.cells().map(|(_, value)| value).collect::<Vec<CellValue>>(), values);
+    }
+
+    #[test]
+    fn test_neighbourhood_states() {
+        let mut store = DenseStore::new(2);
+        store.insert(&[0, 0], CellValue::Set);
+        store.insert(&[1, 1], CellValue::new(2));
+        store.insert(&[0, 1], CellValue::Unset);
+
+        let inputs = vec![vec![0, 0], vec![1, 1], vec![0, 1], vec![-1, 0], vec![50, 0]];
+        assert_eq!(store.neighbourhood_states(&[0, 0], &inputs),
+            vec![CellValue::Set, CellValue::new(2), CellValue::Unset, CellValue::Unset, CellValue::Unset]);
+        assert_eq!(store.neighbourhood_states(&[1, 1], &inputs)[0], CellValue::new(2));
+    }
+}
@@ -0,0 +1,179 @@
+/*
+* Sparse cell store
+*
+* One Cell per cell of the space, in the order they were added, and an index
+* from their coordinates to their position. Costs several allocations per cell,
+* but the order is the one asynchronous updates of older results went through.
+*
+* The index is keyed by the coordinates themselves, so two cells whose
+* coordinates have the same hash are still two cells.
+*/
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::{BuildHasher, RandomState};
+
+use crate::cell::{Cell, CellValue, Coord};
+use crate::space::CellStore;
+
+// The hasher only spreads the cells of the index, which compares their coordinates
+#[derive(Debug, Clone)]
+pub struct SparseStore<S = RandomState> {
+    cells: Vec<Cell>,
+    // Position of each cell in cells
+    index: HashMap<Coord, usize, S>
+}
+
+impl SparseStore {
+    pub fn new() -> Self {
+        SparseStore::with_hasher(RandomState::new())
+    }
+}
+
+impl<S: BuildHasher> SparseStore<S> {
+    pub fn with_hasher(hasher: S) -> Self {
+        SparseStore {
+            cells: vec![],
+            index: HashMap::with_hasher(hasher)
+        }
+    }
+
+    fn search_cells(&self, coordinates: &[i32]) -> Option<&Cell> {
+        self.cells.get(*self.index.get(coordinates)?)
+    }
+
+    fn search_cells_mut(&mut self, coordinates: &[i32]) -> Option<&mut Cell> {
+        self.cells.get_mut(*self.index.get(coordinates)?)
+    }
+}
+
+impl<S: BuildHasher + Clone + Debug + 'static> CellStore for SparseStore<S> {
+    fn get(&self, coordinates: &[i32]) -> Option<CellValue> {
+        self.search_cells(coordinates).map(|cell| cell.get_value())
+    }
+
+    fn insert(&mut self, coordinates: &[i32], value: CellValue) {
+        if let Some(found_cell) = self.search_cells_mut(coordinates) {
+            found_cell.set_value(value);
+        }
+        else {
+            let mut cell = Cell::new(coordinates.len());
+            cell.set_coordinates(coordinates.to_vec());
+            cell.set_value(value);
+            self.index.insert(Coord::new(coordinates), self.cells.len());
+            self.cells.push(cell);
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn pop(&mut self) -> Option<(Vec<i32>, CellValue)> {
+        let cell = self.cells.pop()?;
+        self.index.remove(cell.get_coordinates().as_slice());
+        Some((cell.get_coordinates().clone(), cell.get_value()))
+    }
+
+    fn cells(&self) -> Box<dyn Iterator<Item = (Vec<i32>, CellValue)> + '_> {
+        Box::new(self.cells.iter().map(|cell| (cell.get_coordinates().clone(), cell.get_value())))
+    }
+
+    fn for_each_cell(&self, f: &mut dyn FnMut(&[i32], CellValue)) {
+        for cell in self.cells.iter() {
+            f(cell.get_coordinates(), cell.get_value());
+        }
+    }
+
+    fn values(&self) -> Box<dyn Iterator<Item = CellValue> + '_> {
+        Box::new(self.cells.iter().map(|cell| cell.get_value()))
+    }
+
+    fn set_values(&mut self, values: &[CellValue]) {
+        for (cell, value) in self.cells.iter_mut().zip(values) {
+            cell.set_value(*value);
+        }
+    }
+
+    // The coordinates of each cell and of its key, without what a Cell keeps besides
+    fn heap_bytes(&self) -> usize {
+        let coordinates: usize = self.cells.iter().map(|cell| cell.len() * size_of::<i32>()).sum();
+        self.cells.capacity() * size_of::<Cell>() + self.index.capacity() * size_of::<(Coord, usize)>() + 2 * coordinates
+    }
+
+    fn clone_box(&self) -> Box<dyn CellStore> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::hash::{BuildHasherDefault, Hasher};
+
+    // Every key has the same hash
+    #[derive(Debug, Clone, Default)]
+    struct CollidingHasher;
+
+    impl Hasher for CollidingHasher {
+        fn finish(&self) -> u64 {
+            0
+        }
+
+        fn write(&mut self, _bytes: &[u8]) {}
+    }
+
+    // Three hashes for all keys, from the sum of their bytes
+    #[derive(Debug, Clone, Default)]
+    struct ThreeHashesHasher(u64);
+
+    impl Hasher for ThreeHashesHasher {
+        fn finish(&self) -> u64 {
+            self.0 % 3
+        }
+
+        fn write(&mut self, bytes: &[u8]) {
+            self.0 += bytes.iter().map(|byte| *byte as u64).sum::<u64>();
+        }
+    }
+
+    fn check_colliding<S: BuildHasher + Clone + Debug + 'static>(mut store: SparseStore<S>) {
+        let coordinates: Vec<Vec<i32>> = (-5..5).flat_map(|x| (-5..5).map(move |y| vec![x, y])).collect();
+        for (i, coordinates) in coordinates.iter().enumerate() {
+            store.insert(coordinates, CellValue::from(i % 3 == 0));
+        }
+        assert_eq!(store.len(), coordinates.len());
+        for (i, coordinates) in coordinates.iter().enumerate() {
+            assert_eq!(store.get(coordinates), Some(CellValue::from(i % 3 == 0)));
+        }
+        assert_eq!(store.get(&[5, 0]), None);
+
+        // Setting a cell leaves every colliding cell as it was
+        store.insert(&[0, 0], CellValue::new(2));
+        assert_eq!(store.len(), coordinates.len());
+        assert_eq!(store.get(&[0, 0]), Some(CellValue::new(2)));
+        assert_eq!(store.get(&[0, 1]), Some(CellValue::from(false)));
+        assert_eq!(store.values().filter(|value| *value == CellValue::new(2)).count(), 1);
+    }
+
+    #[test]
+    fn test_colliding_hashes() {
+        check_colliding(SparseStore::with_hasher(BuildHasherDefault::<CollidingHasher>::default()));
+        check_colliding(SparseStore::with_hasher(BuildHasherDefault::<ThreeHashesHasher>::default()));
+        check_colliding(SparseStore::new());
+    }
+
+    #[test]
+    fn test_dimensions() {
+        // Coordinates of any length are a key
+        let mut store = SparseStore::new();
+        let far: Vec<i32> = (0..10).collect();
+        store.insert(&far, CellValue::Set);
+        store.insert(&[0; 10], CellValue::Unset);
+        assert_eq!(store.get(&far), Some(CellValue::Set));
+        assert_eq!(store.get(&[0; 10]), Some(CellValue::Unset));
+        assert_eq!(store.get(&[0; 9]), None);
+        assert_eq!(store.pop(), Some((vec![0; 10], CellValue::Unset)));
+        assert_eq!(store.get(&[0; 10]), None);
+    }
+}
@@ -1,16 +1,210 @@
+pub mod checkpoint;
+mod parallel;
+
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::io;
 use std::io::prelude::*;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
 
 use crate::cell::CellValue;
 use crate::cell::Cell;
-use crate::rule::Action;
-use crate::rule::Rule;
-use crate::space::Space;
+use crate::rule::{Action, Actions, Family, Rule};
+use crate::rule_set::{Policy, RuleSet};
+use crate::measure::{self, Measure, Population};
+use crate::neighbourhood::Neighbourhood;
+use crate::space::{Backend, Space, UpdateMode};
+use crate::topology::Topology;
+use crate::state_explorer::checkpoint::{Checkpoint, CheckpointWriter};
+
+// Memory taken by the results of the functions of a dimension, see Functions
+const FUNCTION_CACHE_BYTES: usize = 16 << 20;
+
+// A rule set generating the expected sequence
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleMatch {
+    pub dimension: usize,
+    // Position of the rule set in the enumeration of its dimension, starting at 1
+    pub index: usize,
+    pub rule_set: RuleSet
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DimensionResult {
+    pub dimension: usize,
+    pub rules_explored: usize,
+    pub permutations_explored: usize,
+    // Whether every rule of the dimension was explored
+    pub exhausted: bool,
+    // Candidates explored without simulating them, as they compute the same function as an
+    // earlier candidate whose result was remembered, see Functions. A lower bound of the
+    // candidates repeating a function: the results of some are forgotten, a resumed
+    // exploration doesn't know those from before its checkpoint, and with several threads
+    // a worker only knows those of the units merged before it took its own
+    pub pruned: usize,
+    pub matches: Vec<RuleMatch>
+}
+
+impl DimensionResult {
+    pub fn new(dim_len: usize) -> Self {
+        DimensionResult {
+            dimension: dim_len,
+            rules_explored: 0,
+            permutations_explored: 0,
+            exhausted: false,
+            pruned: 0,
+            matches: vec![]
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExplorationResult {
+    pub expected_num_set_cells: Vec<usize>,
+    pub dimensions: Vec<DimensionResult>
+}
+
+impl ExplorationResult {
+    pub fn found(&self) -> bool {
+        self.dimensions.iter().any(|dimension| !dimension.matches.is_empty())
+    }
+
+    pub fn matches(&self) -> Vec<&RuleMatch> {
+        self.dimensions.iter().flat_map(|dimension| dimension.matches.iter()).collect()
+    }
+
+    pub fn first_match(&self) -> Option<&RuleMatch> {
+        self.dimensions.iter().flat_map(|dimension| dimension.matches.iter()).next()
+    }
+}
+
+// Position of a candidate in the enumeration of its dimension
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cursor {
+    pub dimension: usize,
+    // Position of the candidate, starting at 1
+    pub index: usize,
+    // Counter of the candidate's rule, starting at 1
+    pub rule_number: usize,
+    pub rule_set: RuleSet
+}
+
+impl Cursor {
+    pub fn new(dim_len: usize) -> Self {
+        Cursor::with_neighbourhood(dim_len, Neighbourhood::Moore(1))
+    }
+
+    pub fn with_neighbourhood(dim_len: usize, neighbourhood: Neighbourhood) -> Self {
+        Cursor::with_family(dim_len, neighbourhood, Family::Tree)
+    }
+
+    pub fn with_family(dim_len: usize, neighbourhood: Neighbourhood, family: Family) -> Self {
+        Cursor::with_rule(dim_len, Rule::with_family(dim_len, neighbourhood, family))
+    }
+
+    // Cursor on the first candidate of the dimension, the given rule
+    pub fn with_rule(dim_len: usize, rule: Rule) -> Self {
+        Cursor::with_rule_set(dim_len, RuleSet::from_rule(&rule))
+    }
+
+    pub fn with_rule_set(dim_len: usize, rule_set: RuleSet) -> Self {
+        Cursor {
+            dimension: dim_len,
+            index: 1,
+            rule_number: 1,
+            rule_set
+        }
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.rule_set.has_next_candidate()
+    }
+
+    pub fn advance(&mut self) {
+        if !self.rule_set.has_next_eval_permutation() {
+            self.rule_number += 1;
+        }
+        self.rule_set.generate_next_candidate();
+        self.index += 1;
+    }
+}
+
+// Results of the functions computed by the candidates explored in a dimension, by their
+// fingerprint, see RuleSet::fingerprint. Each fingerprint hashes to a slot keeping the last
+// function stored there, so the memory taken stays bounded: a function pushed out of its
+// slot is simulated again by the next candidate computing it, which gives the same result
+#[derive(Default)]
+struct Functions {
+    slots: Vec<Option<(Vec<u8>, bool)>>
+}
+
+impl Functions {
+    // Whether the function matched, None when it isn't remembered
+    fn get(&self, fingerprint: &[u8]) -> Option<bool> {
+        match self.slots.get(self.slot(fingerprint))? {
+            Some((stored, matched)) if stored.as_slice() == fingerprint => Some(*matched),
+            _ => None
+        }
+    }
+
+    fn insert(&mut self, fingerprint: Vec<u8>, matched: bool) {
+        // Fingerprints of a dimension all have the same length
+        if self.slots.is_empty() {
+            let slot_len = fingerprint.len() + mem::size_of::<Option<(Vec<u8>, bool)>>();
+            self.slots = vec![None; (FUNCTION_CACHE_BYTES / slot_len).max(1)];
+        }
+        let slot = self.slot(&fingerprint);
+        self.slots[slot] = Some((fingerprint, matched));
+    }
+
+    fn slot(&self, fingerprint: &[u8]) -> usize {
+        let mut hasher = DefaultHasher::new();
+        fingerprint.hash(&mut hasher);
+        hasher.finish() as usize % self.slots.len().max(1)
+    }
+}
 
 pub struct StateExplorer {
     min_dimensions: usize,
     max_dimensions: usize,
-    expected_num_set_cells: Vec<usize>
+    expected_num_set_cells: Vec<usize>,
+    // Clears the terminal and prints every candidate while exploring
+    verbose: bool,
+    // Drops into the interactive emulator once a rule is found
+    emulate_on_match: bool,
+    // Number of worker threads simulating candidates, 1 explores on the calling thread
+    threads: usize,
+    // File and interval of checkpoints
+    checkpoint: Option<(PathBuf, Duration)>,
+    update_mode: UpdateMode,
+    // Storage of the cells of the simulated spaces. Both stores list cells in the order they
+    // were added, so the results don't depend on it and it isn't saved in checkpoints
+    backend: Backend,
+    // Measure of each generation compared with the expected sequence
+    measure: Box<dyn Measure>,
+    // Decides the elements after a cycle of generations without simulating them. Only
+    // for synchronous updates, rules leaving cells without set neighbours unset and
+    // measures of the set cells wherever they are, see cycles_decide_measures
+    prune_cycles: bool,
+    // Gives a candidate whose fingerprint is an earlier one's that candidate's result
+    // without simulating it. The fingerprint is the next state of every configuration
+    // of the neighbourhood, all a simulation reads of the rules
+    dedup: bool,
+    // Cells the rules read
+    neighbourhood: Neighbourhood,
+    // Boundaries of the simulated spaces
+    topology: Topology,
+    // Rules explored
+    family: Family,
+    // Actions enumerated for each condition of the tree family
+    actions: Actions,
+    // Number of states of the cells
+    states: usize,
+    // Number of rules of each candidate, and how they combine
+    rule_set_len: usize,
+    policy: Policy
 }
 
 impl StateExplorer {
@@ -19,209 +213,1053 @@ impl StateExplorer {
             min_dimensions: min_dims,
             max_dimensions : max_dims,
             // Hard-coding to prime numbers for now
-            expected_num_set_cells: expected_set
+            expected_num_set_cells: expected_set,
+            verbose: true,
+            emulate_on_match: !cfg!(test),
+            threads: 1,
+            checkpoint: None,
+            update_mode: UpdateMode::Synchronous,
+            backend: Backend::Sparse,
+            measure: Box::new(Population),
+            prune_cycles: true,
+            dedup: true,
+            neighbourhood: Neighbourhood::Moore(1),
+            topology: Topology::infinite(),
+            family: Family::Tree,
+            actions: Actions::new(),
+            states: 2,
+            rule_set_len: 1,
+            policy: Policy::FirstMatch
+        }
+    }
+
+    // Explorer continuing the exploration of the checkpoint, see StateExplorer::resume
+    pub fn from_checkpoint(checkpoint: &Checkpoint) -> Self {
+        let mut explorer = StateExplorer::new(checkpoint.min_dimensions, checkpoint.max_dimensions, checkpoint.expected_num_set_cells.clone());
+        explorer.set_update_mode(checkpoint.update_mode);
+        // Checkpoint::from_text only accepts known measures
+        explorer.set_measure(measure::from_name(&checkpoint.measure).unwrap());
+        explorer.set_neighbourhood(checkpoint.neighbourhood);
+        explorer.set_topology(&checkpoint.topology);
+        explorer.set_family(checkpoint.family);
+        explorer.set_actions(checkpoint.actions);
+        explorer.set_states(checkpoint.states);
+        explorer.set_rule_set(checkpoint.rule_set_len, checkpoint.policy);
+        explorer
+    }
+
+    pub fn set_verbose(&mut self, verbose: bool) {
+        self.verbose = verbose;
+    }
+
+    pub fn set_emulate_on_match(&mut self, emulate_on_match: bool) {
+        self.emulate_on_match = emulate_on_match;
+    }
+
+    // 0 uses every available core. With more than one thread, the last candidate of
+    // each unit of candidates is printed as it's merged, without its generations
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = if threads == 0 {
+            thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+        }
+        else {
+            threads
+        };
+    }
+
+    pub fn set_update_mode(&mut self, update_mode: UpdateMode) {
+        self.update_mode = update_mode;
+    }
+
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.backend = backend;
+    }
+
+    pub fn set_measure(&mut self, measure: Box<dyn Measure>) {
+        self.measure = measure;
+    }
+
+    pub fn set_prune_cycles(&mut self, prune_cycles: bool) {
+        self.prune_cycles = prune_cycles;
+    }
+
+    pub fn set_dedup(&mut self, dedup: bool) {
+        self.dedup = dedup;
+    }
+
+    pub fn set_neighbourhood(&mut self, neighbourhood: Neighbourhood) {
+        self.neighbourhood = neighbourhood;
+    }
+
+    pub fn set_topology(&mut self, topology: &Topology) {
+        self.topology = topology.clone();
+    }
+
+    // The family has to support the neighbourhood in every dimension explored, see Family::supports
+    pub fn set_family(&mut self, family: Family) {
+        self.family = family;
+    }
+
+    pub fn set_actions(&mut self, actions: Actions) {
+        self.actions = actions;
+    }
+
+    // The family has to support the number of states, see Family::supports_states
+    pub fn set_states(&mut self, states: usize) {
+        self.states = states;
+    }
+
+    // Explores sets of len rules combined by the policy instead of single rules
+    pub fn set_rule_set(&mut self, len: usize, policy: Policy) {
+        self.rule_set_len = len.max(1);
+        self.policy = policy;
+    }
+
+    // First candidate of the dimension
+    fn first_cursor(&self, dim_len: usize, family: Family) -> Cursor {
+        let rule = Rule::first(dim_len, self.neighbourhood, family, self.actions).with_states(self.states);
+        Cursor::with_rule_set(dim_len, RuleSet::first(self.rule_set_len, self.policy, rule))
+    }
+
+    // Fingerprint of the candidate, see RuleSet::fingerprint, None when not deduplicating.
+    // Rules of the other families are numbered by their function, so single ones never repeat
+    fn fingerprint(&self, rule_set: &RuleSet) -> Option<Vec<u8>> {
+        if !self.dedup || (rule_set.len() == 1 && self.family != Family::Tree) {
+            return None;
         }
+        rule_set.fingerprint()
+    }
+
+    // Periodically saves the position of the exploration, so it can be resumed
+    pub fn set_checkpoint(&mut self, path: &Path, interval: Duration) {
+        self.checkpoint = Some((path.to_path_buf(), interval));
     }
 
     pub fn explore(&self) -> bool {
+        match self.search() {
+            Some((dim_len, rule_set)) => {
+                if self.emulate_on_match {
+                    self.emulate_rule_on_user_input(&rule_set, dim_len);
+                }
+                true
+            },
+            None => false
+        }
+    }
+
+    // Returns the first rule set (and its dimension) generating the expected sequence
+    pub fn search(&self) -> Option<(usize, RuleSet)> {
+        let result = self.explore_rules(false);
+        if !result.found() {
+            println!("Found no rule with dimensions between {} and {} that can generate sequence {:?}", self.min_dimensions, self.max_dimensions, self.expected_num_set_cells);
+        }
+
+        result.first_match().map(|found| (found.dimension, found.rule_set.clone()))
+    }
+
+    // Explores every rule of every dimension, collecting all the rules
+    // generating the expected sequence
+    pub fn search_all(&self) -> ExplorationResult {
+        self.explore_rules(true)
+    }
+
+    // Unless exhaustive, stops at the first dimension with a matching rule,
+    // and within it at the first matching rule
+    pub fn explore_rules(&self, exhaustive: bool) -> ExplorationResult {
+        let cursor = self.first_cursor(self.min_dimensions, self.family);
+        self.explore_rules_from(exhaustive, vec![], DimensionResult::new(cursor.dimension), cursor)
+    }
+
+    // Continues the exploration saved in the checkpoint
+    pub fn resume(&self, checkpoint: Checkpoint) -> Result<ExplorationResult, String> {
+        if checkpoint.expected_num_set_cells != self.expected_num_set_cells
+            || checkpoint.min_dimensions != self.min_dimensions
+            || checkpoint.max_dimensions != self.max_dimensions
+            || checkpoint.update_mode != self.update_mode
+            || checkpoint.measure != self.measure.name()
+            || checkpoint.neighbourhood != self.neighbourhood
+            || checkpoint.topology != self.topology
+            || checkpoint.family != self.family
+            || checkpoint.actions != self.actions
+            || checkpoint.states != self.states
+            || checkpoint.rule_set_len != self.rule_set_len
+            || checkpoint.policy != self.policy {
+            return Err(format!("Checkpoint is for sequence {:?} with dimensions between {} and {}, updated {}, matching {}, with {} rules of {} states in the {} neighbourhood of a {} space, actions {} and sets of {} rules by {}", checkpoint.expected_num_set_cells, checkpoint.min_dimensions, checkpoint.max_dimensions, checkpoint.update_mode.name(), checkpoint.measure, checkpoint.family.name(), checkpoint.states, checkpoint.neighbourhood.name(), checkpoint.topology.name(), checkpoint.actions.name(), checkpoint.rule_set_len, checkpoint.policy.name()));
+        }
+
+        let mut before = DimensionResult::new(checkpoint.cursor.dimension);
+        before.pruned = checkpoint.pruned;
+        before.matches = checkpoint.matches;
+        Ok(self.explore_rules_from(checkpoint.exhaustive, checkpoint.explored, before, checkpoint.cursor))
+    }
+
+    // explored are the results of the dimensions before the cursor's,
+    // and before what was found in the cursor's dimension before the cursor
+    fn explore_rules_from(&self, exhaustive: bool, explored: Vec<DimensionResult>, before: DimensionResult, cursor: Cursor) -> ExplorationResult {
+        let mut writer = self.checkpoint.as_ref().map(|(path, interval)| {
+            CheckpointWriter::new(path, *interval, Checkpoint {
+                expected_num_set_cells: self.expected_num_set_cells.clone(),
+                min_dimensions: self.min_dimensions,
+                max_dimensions: self.max_dimensions,
+                update_mode: self.update_mode,
+                measure: self.measure.name(),
+                neighbourhood: self.neighbourhood,
+                topology: self.topology.clone(),
+                family: self.family,
+                actions: self.actions,
+                states: self.states,
+                rule_set_len: self.rule_set_len,
+                policy: self.policy,
+                exhaustive,
+                explored: explored.clone(),
+                matches: vec![],
+                pruned: 0,
+                cursor: cursor.clone()
+            })
+        });
+
+        let mut result = ExplorationResult {
+            expected_num_set_cells: self.expected_num_set_cells.clone(),
+            dimensions: explored
+        };
+        // A checkpoint written once the search is over has its cursor past the last dimension to explore
+        let done = cursor.dimension > self.max_dimensions || (!exhaustive && result.found());
+        let mut start = (!done).then_some((cursor, before));
+
         // Explore all dimensions
-        for dim_len in self.min_dimensions..self.max_dimensions+1 {
-            let mut rule = Rule::new(dim_len);
-            let mut rule_counter = 0;
-            let mut permutation_counter = 0;
-            // Explore all rules of given dimension
-            loop {
-                rule_counter += 1;
-                // For all evaluation permutations 
-                loop {
-                    print!("{esc}[2J{esc}[1;1H", esc = 27 as char);
-                    permutation_counter += 1;
-                    let mut space: Space = Space::new(dim_len);
-                    space.set_rule(&rule);
-    
-                    let mut __cell: Cell = Cell::new(dim_len);
-                    __cell.set();
-                    space.push_cell(&__cell);
-    
-                    let mut all_matched: bool = true;
-                    let mut match_counter = 0;
-                    for el in self.expected_num_set_cells.clone() {
-                        print!("{esc}[2J{esc}[1;1H", esc = 27 as char);
-                        println!("Exploring dimension {}", dim_len);
-                        println!("Explored {rule_counter} rules and {permutation_counter} permutations. The last one was: ");
-                        rule.print();
-                        rule.debug_print();
-                        space.debug_print();
-                        if space.find_number_of_cells(CellValue::Set) != el {
-                            all_matched = false;
-                            break;
-                        }
-                        match_counter += 1;
-                        space.generate_next_iteration();
-                        for _cell in space.cells.clone().iter() {
-                            let mut c = _cell.clone();
-                            StateExplorer::apply_rule_if_applicable(&rule, &mut c, &space);
-                            space.push_cell(&c);
-                        }
-    
-                        if cfg!(debug_assertions) {
-                            println!("Matched {} elements.", match_counter);
-                        }
-                    }
-    
-                    if cfg!(debug_assertions) {
-                        println!("\tDone exploring rule {}", rule_counter);
-                    }
-                    
-                    if all_matched {
-                        println!("All elements matched for rule");
-                        rule.print();
-
-                        if !cfg!(test) {
-                            self.emulate_rule_on_user_input(&rule, dim_len);
-                        }
-                        return true;
-                    }
+        while let Some((cursor, before)) = start.take() {
+            let dim_len = cursor.dimension;
+            let dimension_result = if self.threads > 1 {
+                parallel::explore_dimension(self, cursor, before, exhaustive, self.threads, &mut writer)
+            }
+            else {
+                self.explore_dimension(cursor, before, exhaustive, &mut writer)
+            };
+            println!("Done exploring dimension {}, explored {} rules and {} permutations, {} of them pruned as they were found to compute the function of an earlier one", dim_len, dimension_result.rules_explored, dimension_result.permutations_explored, dimension_result.pruned);
 
-                    if !rule.has_next_eval_permutation() {
-                        break;
+            let found = !dimension_result.matches.is_empty();
+            if let Some(writer) = writer.as_mut() {
+                writer.finish_dimension(&dimension_result);
+            }
+            result.dimensions.push(dimension_result);
+
+            // Past the last dimension the cursor only marks the end of the search,
+            // in a dimension the family may not have rules for
+            let family = match self.family.supports(dim_len + 1, self.neighbourhood) {
+                Err(_) if dim_len >= self.max_dimensions => Family::Tree,
+                _ => self.family
+            };
+            let next = self.first_cursor(dim_len + 1, family);
+            if let Some(writer) = writer.as_mut() {
+                writer.write(&[], 0, &next);
+            }
+
+            if (found && !exhaustive) || dim_len >= self.max_dimensions {
+                break;
+            }
+            start = Some((next, DimensionResult::new(dim_len + 1)));
+        }
+
+        result
+    }
+
+    // Functions of the candidates before the cursor aren't known, so
+    // candidates computing them are simulated again
+    fn explore_dimension(&self, cursor: Cursor, before: DimensionResult, exhaustive: bool, writer: &mut Option<CheckpointWriter>) -> DimensionResult {
+        let dim_len = cursor.dimension;
+        let mut result = before;
+        let mut functions = Functions::default();
+        let mut cursor = cursor;
+
+        // Explore all rules and evaluation permutations of given dimension
+        loop {
+            result.rules_explored = cursor.rule_number;
+            result.permutations_explored = cursor.index;
+
+            let fingerprint = self.fingerprint(&cursor.rule_set);
+            let matched = match fingerprint.as_deref().and_then(|fingerprint| functions.get(fingerprint)) {
+                Some(matched) => {
+                    result.pruned += 1;
+                    matched
+                },
+                None => {
+                    let matched = self.generates_expected_sequence(&cursor.rule_set, dim_len, Some(&result));
+                    if let Some(fingerprint) = fingerprint {
+                        functions.insert(fingerprint, matched);
                     }
-                    rule.generate_next_eval_permutation();
+                    matched
                 }
+            };
+            if matched {
+                println!("All elements matched for rule");
+                cursor.rule_set.print();
+                result.matches.push(RuleMatch {
+                    dimension: dim_len,
+                    index: cursor.index,
+                    rule_set: cursor.rule_set.clone()
+                });
 
-                if !rule.has_next() {
-                    break;
+                if !exhaustive {
+                    return result;
                 }
-                rule.generate_next();
             }
 
-            println!("Done exploring dimension {}, explored {} rules and {} permutations", dim_len, rule_counter, permutation_counter);
+            if !cursor.has_next() {
+                break;
+            }
+            cursor.advance();
+
+            if let Some(writer) = writer.as_mut().filter(|writer| writer.is_due()) {
+                writer.write(&result.matches, result.pruned, &cursor);
+            }
         }
 
-        println!("Found no rule with dimensions between {} and {} that can generate sequence {:?}", self.min_dimensions, self.max_dimensions, self.expected_num_set_cells);
-        
-        false
+        result.exhausted = true;
+        result
+    }
+
+    // Clears the terminal and prints the candidate being explored
+    fn print_progress(rule_set: &RuleSet, progress: &DimensionResult) {
+        print!("{esc}[2J{esc}[1;1H", esc = 27 as char);
+        println!("Exploring dimension {}", progress.dimension);
+        println!("Explored {} rules and {} permutations. The last one was: ", progress.rules_explored, progress.permutations_explored);
+        rule_set.print();
+    }
+
+    // progress is only used for printing the candidate while exploring
+    fn generates_expected_sequence(&self, rule_set: &RuleSet, dim_len: usize, progress: Option<&DimensionResult>) -> bool {
+        let verbose = self.verbose && progress.is_some();
+
+        let mut space: Space = Space::with_backend(dim_len, self.backend);
+        space.set_rule_set(rule_set);
+        space.set_topology(&self.topology);
+
+        let mut __cell: Cell = Cell::new(dim_len);
+        __cell.set();
+        space.push_cell(&__cell);
+
+        let mut prune_cycles = self.prune_cycles && self.cycles_decide_measures(rule_set, dim_len);
+        let mut all_matched: bool = true;
+        let mut match_counter = 0;
+        for (generation, el) in self.expected_num_set_cells.iter().enumerate() {
+            if let Some(progress) = progress.filter(|_| verbose) {
+                StateExplorer::print_progress(rule_set, progress);
+                rule_set.debug_print();
+                space.debug_print();
+            }
+            if !self.measure.matches(&space, *el) {
+                all_matched = false;
+                break;
+            }
+            match_counter += 1;
+            if let Some(recurrence) = space.record_generation().filter(|_| prune_cycles) {
+                match self.matches_after_cycle(generation, recurrence.period) {
+                    Some(matched) => {
+                        all_matched = matched;
+                        break;
+                    },
+                    // Only the generations tell, and they keep repeating
+                    None => prune_cycles = false
+                }
+            }
+            space.generate_next_iteration();
+            space.apply_rule(self.update_mode);
+
+            if verbose && cfg!(debug_assertions) {
+                println!("Matched {} elements.", match_counter);
+            }
+        }
+
+        if let Some(progress) = progress.filter(|_| verbose && cfg!(debug_assertions)) {
+            println!("\tDone exploring rule {}", progress.rules_explored);
+        }
+
+        all_matched
+    }
+
+    // Whether the generations of the rule set, once they repeat, keep repeating with
+    // their measures. Each generation only depends on the one before it when the cells
+    // are updated together, and on its set cells alone when cells with no set neighbour
+    // stay unset, as only the cells around set cells are in the space
+    fn cycles_decide_measures(&self, rule_set: &RuleSet, dim_len: usize) -> bool {
+        let quiescent = vec![CellValue::Unset; rule_set.get_neighbourhood().inputs(dim_len).len()];
+        self.update_mode == UpdateMode::Synchronous
+            && self.measure.translation_invariant()
+            && rule_set.next_state(&quiescent) == CellValue::Unset
+    }
+
+    // Whether the elements after the generation match, the generations repeating with the
+    // period from it on. None when only the generations can tell
+    fn matches_after_cycle(&self, generation: usize, period: usize) -> Option<bool> {
+        let expected = &self.expected_num_set_cells;
+        // Each generation matches what the one a period before it matched, its own element
+        // up to this generation, then the element it was itself compared with
+        if (generation + 1..expected.len()).all(|i| expected[i] == expected[i - period]) {
+            Some(true)
+        }
+        else if self.measure.single_valued() {
+            Some(false)
+        }
+        else {
+            None
+        }
+    }
+
+    fn emulate_rule_on_user_input(&self, rule_set: &RuleSet, dim_len: usize) {
+        StateExplorer::emulate_rule_set(rule_set, dim_len, self.update_mode, &self.topology, None);
+    }
+
+    pub fn emulate_rule(rule: &Rule, dim_len: usize, update_mode: UpdateMode, topology: &Topology, generations: Option<usize>) {
+        StateExplorer::emulate_rule_set(&RuleSet::from_rule(rule), dim_len, update_mode, topology, generations);
     }
 
-    fn emulate_rule_on_user_input(&self, rule: &Rule, dim_len: usize) {
+    // Emulates the rules from a single set cell. With a generation count, prints
+    // that many generations and returns, otherwise waits for a key press
+    // between generations until interrupted
+    pub fn emulate_rule_set(rule_set: &RuleSet, dim_len: usize, update_mode: UpdateMode, topology: &Topology, generations: Option<usize>) {
         let mut stdin = io::stdin();
         let mut stdout = io::stdout();
 
         let mut cell = Cell::new(dim_len);
         cell.set();
         let mut space = Space::new(dim_len);
-        space.set_rule(rule);
+        space.set_rule_set(rule_set);
+        space.set_topology(topology);
         space.push_cell(&cell);
 
         let mut iter_counter = 1;
+        let mut recurrence = None;
 
         loop {
-            print!("{esc}[2J{esc}[1;1H", esc = 27 as char);
-            println!("All elements matched for sequence {:?}", self.expected_num_set_cells);
-
-            println!("Emulating rule ");
-            rule.print();
+            if generations.is_none() {
+                print!("{esc}[2J{esc}[1;1H", esc = 27 as char);
+                println!("Emulating rule ");
+                rule_set.print();
+            }
             println!("Iteration: {}", iter_counter);
+            // The first recurrence, later generations repeating those of the cycle
+            if recurrence.is_none() {
+                recurrence = space.record_generation();
+            }
+            if let Some(recurrence) = recurrence {
+                println!("Generations repeat: {}", recurrence);
+            }
 
             space.generate_next_iteration();
             space.print();
             iter_counter += 1;
-            write!(stdout, "Press any key for generating next iteration. Ctrl + C to exit").unwrap();
-            stdout.flush().unwrap();
-            // Read a single byte and discard
-            let _ = stdin.read(&mut [0u8]).unwrap();
 
-            for cell in space.cells.clone().iter() {
-                let mut _cell = cell.clone();
-                StateExplorer::apply_rule_if_applicable(rule, &mut _cell, &space);
-                space.push_cell(&_cell);
+            if let Some(generations) = generations {
+                if iter_counter > generations {
+                    return;
+                }
             }
+            else {
+                write!(stdout, "Press any key for generating next iteration. Ctrl + C to exit").unwrap();
+                stdout.flush().unwrap();
+                // Read a single byte and discard
+                let _ = stdin.read(&mut [0u8]).unwrap();
+            }
+
+            space.apply_rule(update_mode);
         }
     }
 
-    pub fn apply_rule_if_applicable(rule: &Rule, cell: &mut Cell, space: &Space) {
-        if StateExplorer::is_rule_applicable(rule, cell, space) {
-            StateExplorer::apply_rule(rule, cell);
+    // Rules of a set only combine their next values, as the rules
+    // acting on a cell depend on the policy
+    pub fn apply_rule_set(rule_set: &RuleSet, cell: &mut Cell, space: &Space) {
+        cell.set_value(StateExplorer::next_value(rule_set, cell.get_coordinates(), cell.get_value(), space));
+    }
+
+    // Value of the cell at the coordinates in the next generation
+    pub fn next_value(rule_set: &RuleSet, coordinates: &[i32], value: CellValue, space: &Space) -> CellValue {
+        if let [rule] = rule_set.get_rules() {
+            return StateExplorer::next_value_of_rule(rule, coordinates, value, space);
         }
+
+        rule_set.next_state(&space.neighbourhood_states(rule_set.get_neighbourhood(), coordinates))
     }
 
-    fn apply_rule(rule: &Rule, cell: &mut Cell) {
-        if rule.result == Action::Set {
-            cell.set();
+    pub fn apply_rule_if_applicable(rule: &Rule, cell: &mut Cell, space: &Space) {
+        cell.set_value(StateExplorer::next_value_of_rule(rule, cell.get_coordinates(), cell.get_value(), space));
+    }
+
+    fn next_value_of_rule(rule: &Rule, coordinates: &[i32], value: CellValue, space: &Space) -> CellValue {
+        // Actions of rules of more states depend on the number of states
+        if rule.get_states() > 2 {
+            rule.next_state(&space.neighbourhood_states(rule.get_neighbourhood(), coordinates))
+        }
+        else if StateExplorer::is_rule_applicable(rule, coordinates, space) {
+            StateExplorer::apply_rule(rule.result, value)
         }
-        else if rule.result == Action::Unset {
-            cell.unset();
+        else if let Some(else_result) = rule.else_result {
+            StateExplorer::apply_rule(else_result, value)
         }
         else {
-            cell.flip();
+            value
         }
     }
 
-    fn is_rule_applicable(rule: &Rule, cell: &mut Cell, space: &Space) -> bool {
-        // Always reset before applying rules
-        cell.reset_explore();
-
-        // truth values to be applied to condition
-        let mut v: Vec<bool> = vec![];
-        
-        let cell_coordinates: Vec<i32> = cell.get_nearby_coordinate();
-        let cell_in_space = space.search_cells(&cell_coordinates);
-        if cell_in_space == None {
-            // Cell unset
-            v.push(false);
-        }
-        else {
-            if cell_in_space.unwrap().get_value() == CellValue::Set {
-                v.push(true);
-            }
-            else {
-                v.push(false);
-            }
+    fn apply_rule(action: Action, value: CellValue) -> CellValue {
+        match action {
+            Action::Set => CellValue::Set,
+            Action::Unset => CellValue::Unset,
+            Action::State(state) => CellValue::new(state as usize),
+            Action::Flip if value == CellValue::Set => CellValue::Unset,
+            Action::Flip => CellValue::Set
         }
+    }
 
-        while cell.has_unexplored_nearby_cell() {
-            cell.generate_next_unexplored_nearby_cell();
-            let cell_coordinates: Vec<i32> = cell.get_nearby_coordinate();
-            
-            let cell_in_space = space.search_cells(&cell_coordinates);
-            if cell_in_space == None {
-                // Cell unset
-                v.push(false);
-            }
-            else {
-                if cell_in_space.unwrap().get_value() == CellValue::Set {
-                    v.push(true);
-                }
-                else {
-                    v.push(false);
-                }
-            }
+    fn is_rule_applicable(rule: &Rule, coordinates: &[i32], space: &Space) -> bool {
+        let v = StateExplorer::neighbourhood_values(rule.get_neighbourhood(), coordinates, space);
+
+        match rule.get_family() {
+            Family::Tree => rule.evaluate(&v),
+            // Rules of a family give the next value, which their action flips the cell to
+            Family::Elementary => rule.evaluate_elementary(&v) != v[0],
+            Family::Totalistic => rule.evaluate_totalistic(v.iter().filter(|el| **el).count()) != v[0],
+            Family::OuterTotalistic => rule.evaluate_outer_totalistic(v[0], v[1..].iter().filter(|el| **el).count()) != v[0],
+            Family::Generations => rule.evaluate(&v)
         }
+    }
 
-        return rule.evaluate(&v);
+    // Truth values to be applied to conditions, one per cell of the neighbourhood
+    fn neighbourhood_values(neighbourhood: Neighbourhood, coordinates: &[i32], space: &Space) -> Vec<bool> {
+        space.neighbourhood_states(neighbourhood, coordinates).iter().map(|el| *el == CellValue::Set).collect()
     }
 }
 
+#[cfg(test)]
 mod test {
     use super::*;
 
 
+    // The last explorer is only built, as exploring it takes a few seconds
     #[test]
+    #[allow(unused_assignments)]
     fn test_is_rule_applicable() {
         let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,3,5,7,9,11,13,15]);
-        assert_eq!(explorer.explore(), true);
+        assert!(explorer.explore());
 
         explorer = StateExplorer::new(1, 1, vec![1,2,3,4,5,6,7,8,9]);
-        assert_eq!(explorer.explore(), true);
+        assert!(explorer.explore());
 
         explorer = StateExplorer::new(1, 1, vec![1,2,3,5,7,11]);
-        assert_eq!(explorer.explore(), false);
+        assert!(!explorer.explore());
 
         explorer = StateExplorer::new(2, 2, vec![1,2,3,4,5,6,7,8,9]);
-        assert_eq!(explorer.explore(), true);
+        assert!(explorer.explore());
 
         // Warning: This takes a few seconds
         explorer = StateExplorer::new(2, 2, vec![1,3,5,7,9,11,13,15]);
         // assert_eq!(explorer.explore(), true);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_search_all() {
+        let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,3,5,7,9,11,13,15]);
+        explorer.set_verbose(false);
+
+        let first = explorer.search().unwrap();
+        let result = explorer.search_all();
+
+        assert!(result.found());
+        assert_eq!(result.dimensions.len(), 1);
+        assert!(result.dimensions[0].exhausted);
+        assert_eq!(result.dimensions[0].rules_explored, 64);
+        assert_eq!(result.dimensions[0].permutations_explored, 96);
+        assert!(result.matches().len() > 1);
+
+        let first_match = result.first_match().unwrap();
+        assert_eq!(first_match.dimension, first.0);
+        assert_eq!(first_match.rule_set, first.1);
+
+        // Every match is a distinct candidate which reproduces the sequence
+        // at its position in the enumeration
+        let mut previous_index = 0;
+        for found in result.matches() {
+            assert!(found.index > previous_index);
+            previous_index = found.index;
+
+            let mut rule = Rule::new(1);
+            for _ in 1..found.index {
+                rule.generate_next_candidate();
+            }
+            assert_eq!(RuleSet::from_rule(&rule), found.rule_set);
+            assert!(explorer.generates_expected_sequence(&RuleSet::from_rule(&rule), 1, None));
+        }
+
+        explorer = StateExplorer::new(1, 1, vec![1,2,3,5,7,11]);
+        explorer.set_verbose(false);
+        let result = explorer.search_all();
+        assert!(!result.found());
+        assert_eq!(result.first_match(), None);
+    }
+
+    #[test]
+    fn test_match_block_sizes() {
+        // Three cells, then blocks of three moving apart
+        let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,3,3,3,3]);
+        explorer.set_verbose(false);
+        assert!(!explorer.explore_rules(false).found());
+
+        explorer.set_measure(Box::new(measure::BlockSizes(Neighbourhood::Moore(1))));
+        let result = explorer.search_all();
+        assert!(result.found());
+
+        for found in result.matches() {
+            let mut space = Space::new(1);
+            space.set_rule_set(&found.rule_set);
+            let mut cell = Cell::new(1);
+            cell.set();
+            space.push_cell(&cell);
+            for el in [1,3,3,3,3] {
+                assert!(space.component_sizes(Neighbourhood::Moore(1)).contains(&el));
+                space.generate_next_iteration();
+                space.apply_rule(UpdateMode::Synchronous);
+            }
+        }
+    }
+
+    #[test]
+    fn test_measures() {
+        // One cell, then a line growing by two cells each generation
+        let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,2,2,2,2]);
+        explorer.set_verbose(false);
+        explorer.set_measure(Box::new(measure::NewlySet));
+        let with_newly_set = explorer.search_all();
+
+        explorer = StateExplorer::new(1, 1, vec![1,3,5,7,9]);
+        explorer.set_verbose(false);
+        explorer.set_measure(Box::new(measure::BoundingBoxVolume));
+        let with_bounding_box = explorer.search_all();
+
+        explorer.set_measure(Box::new(Population));
+        let with_population = explorer.search_all();
+
+        // Lines are found by all three, and a full line is its bounding box
+        for found in with_population.matches() {
+            assert!(with_newly_set.matches().contains(&found));
+            assert!(with_bounding_box.matches().contains(&found));
+        }
+    }
+
+    #[test]
+    fn test_von_neumann_neighbourhood() {
+        // In one dimension both neighbourhoods are the same cells
+        let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,3,5,7,9]);
+        explorer.set_verbose(false);
+        let moore = explorer.search_all();
+        explorer.set_neighbourhood(Neighbourhood::VonNeumann(1));
+        let von_neumann = explorer.search_all();
+        assert_eq!(von_neumann.dimensions[0].permutations_explored, moore.dimensions[0].permutations_explored);
+        assert_eq!(von_neumann.matches().len(), moore.matches().len());
+
+        // Diamonds growing by one cell in every direction, with rules over 5 cells instead of 9
+        explorer = StateExplorer::new(2, 2, vec![1,5,13,25]);
+        explorer.set_verbose(false);
+        explorer.set_neighbourhood(Neighbourhood::VonNeumann(1));
+        let found = explorer.search().unwrap();
+        assert_eq!(found.0, 2);
+        assert_eq!(found.1.get_neighbourhood(), Neighbourhood::VonNeumann(1));
+    }
+
+    #[test]
+    fn test_neighbourhood_radius() {
+        // Radius 2 reaches two cells on each side, so lines can grow by four cells
+        let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,5,9,13]);
+        explorer.set_verbose(false);
+        assert!(!explorer.explore_rules(false).found());
+
+        explorer.set_neighbourhood(Neighbourhood::Moore(2));
+        let result = explorer.explore_rules(false);
+        let found = result.first_match().unwrap();
+        assert_eq!(found.rule_set.get_rules()[0].get_neighbourhood(), Neighbourhood::Moore(2));
+
+        let mut space = Space::new(1);
+        space.set_rule_set(&found.rule_set);
+        let mut cell = Cell::new(1);
+        cell.set();
+        space.push_cell(&cell);
+        for el in [1,5,9,13] {
+            assert_eq!(space.find_number_of_cells(CellValue::Set), el);
+            space.generate_next_iteration();
+            space.apply_rule(UpdateMode::Synchronous);
+        }
+        assert_eq!(space.search_cells(&[-6]).unwrap().get_value(), CellValue::Set);
+    }
+
+    #[test]
+    fn test_rule_families() {
+        // Wolfram's definition: from a single cell, cells -1, 0 and 1 read 001, 010 and 100
+        let mut rule = Rule::with_family(1, Neighbourhood::Moore(1), Family::Elementary);
+        loop {
+            let number = rule.get_number().unwrap();
+            let mut space = Space::new(1);
+            space.set_rule(&rule);
+            let mut cell = Cell::new(1);
+            cell.set();
+            space.push_cell(&cell);
+            space.generate_next_iteration();
+            space.apply_rule(UpdateMode::Synchronous);
+            for (coordinate, config) in [(-1, 0b001), (0, 0b010), (1, 0b100)] {
+                let value = space.search_cells(&[coordinate]).unwrap().get_value();
+                assert_eq!(value == CellValue::Set, number >> config & 1 == 1);
+            }
+
+            if !rule.has_next_candidate() {
+                break;
+            }
+            rule.generate_next_candidate();
+        }
+
+        // Setting any cell with a set neighbour grows lines, squares and diamonds
+        let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,3,5,7,9]);
+        explorer.set_verbose(false);
+        explorer.set_family(Family::Elementary);
+        let result = explorer.search_all();
+        assert_eq!(result.dimensions[0].permutations_explored, 256);
+        assert!(result.matches().iter().any(|found| found.rule_set.get_rules()[0].get_number() == Some(254)));
+
+        explorer = StateExplorer::new(2, 2, vec![1,9,25,49]);
+        explorer.set_verbose(false);
+        explorer.set_family(Family::Totalistic);
+        let result = explorer.search_all();
+        assert_eq!(result.dimensions[0].permutations_explored, 1024);
+        assert!(result.matches().iter().any(|found| found.rule_set.get_rules()[0].get_number() == Some(0b1111111110)));
+
+        explorer = StateExplorer::new(2, 2, vec![1,5,13,25]);
+        explorer.set_verbose(false);
+        explorer.set_neighbourhood(Neighbourhood::VonNeumann(1));
+        explorer.set_family(Family::OuterTotalistic);
+        let found = explorer.search().unwrap();
+        assert_eq!(found.1.get_rules()[0].get_family(), Family::OuterTotalistic);
+    }
+
+    #[test]
+    fn test_actions() {
+        // Applying a rule to a space gives the next values of its truth table
+        let all = Actions { unset: true, else_action: true };
+        let mut rule = Rule::with_actions(1, Neighbourhood::Moore(1), all);
+        loop {
+            let mut space = Space::new(1);
+            space.set_rule(&rule);
+            for (coordinate, set) in [(-2, true), (-1, false), (0, true), (1, true), (2, false)] {
+                let mut cell = Cell::new(1);
+                cell.set_coordinates(vec![coordinate]);
+                if set {
+                    cell.set();
+                }
+                space.push_cell(&cell);
+            }
+            let table = rule.get_truth_table();
+            let expected: Vec<bool> = (-1..2)
+                .map(|i| [i, i + 1, i - 1].map(|j| space.search_cells(&[j]).is_some_and(|c| c.get_value() == CellValue::Set)))
+                .map(|values| table.evaluate(&values))
+                .collect();
+
+            space.apply_rule(UpdateMode::Synchronous);
+            let values: Vec<bool> = (-1..2).map(|i| space.search_cells(&[i]).unwrap().get_value() == CellValue::Set).collect();
+            assert_eq!(values, expected);
+
+            if !rule.has_next_candidate() {
+                break;
+            }
+            rule.generate_next_candidate();
+        }
+
+        // More actions find more rules, including those of the default actions
+        let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,0,0]);
+        explorer.set_verbose(false);
+        let set_or_flip = explorer.search_all();
+        explorer.set_actions(all);
+        let result = explorer.search_all();
+        assert_eq!(result.dimensions[0].permutations_explored, 320);
+        assert!(result.matches().len() > set_or_flip.matches().len());
+        assert!(result.matches().iter().any(|found| found.rule_set.get_rules()[0].result == Action::Unset));
+        assert!(result.matches().iter().any(|found| found.rule_set.get_rules()[0].else_result.is_some()));
+    }
+
+    #[test]
+    fn test_rule_sets() {
+        // 16 totalistic rules of 3 cells, so 256 pairs
+        let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,3,5,7]);
+        explorer.set_verbose(false);
+        explorer.set_neighbourhood(Neighbourhood::VonNeumann(1));
+        explorer.set_family(Family::Totalistic);
+        let single = explorer.search_all();
+        assert_eq!(single.dimensions[0].permutations_explored, 16);
+
+        explorer.set_rule_set(2, Policy::FirstMatch);
+        let pairs = explorer.search_all();
+        assert_eq!(pairs.dimensions[0].permutations_explored, 256);
+        assert_eq!(pairs.dimensions[0].rules_explored, 256);
+
+        // A rule followed by itself is the rule
+        for found in single.matches() {
+            let rule = &found.rule_set.get_rules()[0];
+            assert!(pairs.matches().iter().any(|pair| pair.rule_set.get_rules() == [rule.clone(), rule.clone()]));
+        }
+        assert!(pairs.matches().len() > single.matches().len());
+
+        // Exploring on several threads gives the same sets, pruning no more than one thread
+        explorer.set_threads(3);
+        let parallel = explorer.search_all();
+        assert_eq!(parallel.matches(), pairs.matches());
+        assert_eq!(parallel.dimensions[0].permutations_explored, pairs.dimensions[0].permutations_explored);
+        assert_eq!(parallel.dimensions[0].rules_explored, pairs.dimensions[0].rules_explored);
+        assert!(parallel.dimensions[0].pruned <= pairs.dimensions[0].pruned);
+    }
+
+    #[test]
+    fn test_topologies() {
+        let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,3,5,7]);
+        explorer.set_verbose(false);
+        let infinite = explorer.search_all();
+        assert!(infinite.found());
+
+        // 5 cells can't grow to 7
+        explorer.set_topology(&Topology::from_name("torus:5").unwrap());
+        let torus = explorer.search_all();
+        assert!(!torus.found());
+        assert_eq!(torus.dimensions[0].permutations_explored, infinite.dimensions[0].permutations_explored);
+
+        // But the first generations are the same
+        explorer = StateExplorer::new(1, 1, vec![1,3,5]);
+        explorer.set_verbose(false);
+        let infinite = explorer.search_all();
+        explorer.set_topology(&Topology::from_name("dead:5").unwrap());
+        assert_eq!(explorer.search_all(), infinite);
+    }
+
+    #[test]
+    fn test_backends() {
+        // Rules acting everywhere read the unset cells around the set ones too
+        let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,3,5,7]);
+        explorer.set_verbose(false);
+        explorer.set_actions(Actions { unset: true, else_action: true });
+        let sparse = explorer.search_all();
+        explorer.set_backend(Backend::Dense);
+        assert_eq!(explorer.search_all(), sparse);
+
+        let mut explorer: StateExplorer = StateExplorer::new(2, 2, vec![1,5,13,25]);
+        explorer.set_verbose(false);
+        explorer.set_neighbourhood(Neighbourhood::VonNeumann(1));
+        explorer.set_family(Family::OuterTotalistic);
+        let sparse = explorer.search_all();
+        assert!(!sparse.matches().is_empty());
+        explorer.set_backend(Backend::Dense);
+        assert_eq!(explorer.search_all(), sparse);
+    }
+
+    #[test]
+    fn test_prune_cycles() {
+        // The same matches whether or not the generations after a cycle are simulated
+        let sequences = [vec![1,3,5,7], vec![1,1,1,1,1,1], vec![1,3,3,3,3,3,4], vec![1,2,1,2,1,2,1], vec![1,0,0,0,0]];
+        for measure in ["population", "newly-set", "blocks:moore", "axis-population:0", "bounding-box"] {
+            for sequence in sequences.iter() {
+                let mut explorer: StateExplorer = StateExplorer::new(1, 1, sequence.clone());
+                explorer.set_verbose(false);
+                explorer.set_measure(measure::from_name(measure).unwrap());
+                let pruned = explorer.search_all();
+                explorer.set_prune_cycles(false);
+                assert_eq!(explorer.search_all(), pruned, "{} {:?}", measure, sequence);
+            }
+        }
+
+        let mut explorer: StateExplorer = StateExplorer::new(2, 2, vec![1,0,0,0,0,0]);
+        explorer.set_verbose(false);
+        explorer.set_neighbourhood(Neighbourhood::VonNeumann(1));
+        explorer.set_family(Family::OuterTotalistic);
+        let pruned = explorer.search_all();
+        assert!(!pruned.matches().is_empty());
+        explorer.set_prune_cycles(false);
+        assert_eq!(explorer.search_all(), pruned);
+
+        // Past a cycle of 2 entered at generation 1, elements repeat every 2
+        let explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,4,2,4,2,4]);
+        assert_eq!(explorer.matches_after_cycle(3, 2), Some(true));
+        let explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,4,2,4,2,2]);
+        assert_eq!(explorer.matches_after_cycle(3, 2), Some(false));
+        assert_eq!(explorer.matches_after_cycle(5, 2), Some(true));
+    }
+
+    #[test]
+    fn test_dedup() {
+        // The same matches whether or not candidates computing the function of an earlier one are simulated
+        let explore = |explorer: &mut StateExplorer, exhaustive: bool| {
+            explorer.set_dedup(true);
+            let deduplicated = explorer.explore_rules(exhaustive);
+            explorer.set_dedup(false);
+            let simulated = explorer.explore_rules(exhaustive);
+            assert_eq!(simulated.dimensions.len(), deduplicated.dimensions.len());
+            for (simulated, deduplicated) in simulated.dimensions.iter().zip(deduplicated.dimensions.iter()) {
+                assert_eq!(simulated.pruned, 0);
+                assert_eq!(simulated.matches, deduplicated.matches);
+                assert_eq!((simulated.rules_explored, simulated.permutations_explored, simulated.exhausted),
+                    (deduplicated.rules_explored, deduplicated.permutations_explored, deduplicated.exhausted));
+            }
+            deduplicated
+        };
+
+        for sequence in [vec![1,3,5,7], vec![1,2,3,4,5,6,7,8,9], vec![1,1,1,1,1]] {
+            let mut explorer: StateExplorer = StateExplorer::new(1, 1, sequence.clone());
+            explorer.set_verbose(false);
+            explorer.set_actions(Actions { unset: true, else_action: true });
+            let result = explore(&mut explorer, true);
+            assert!(result.dimensions[0].pruned > result.dimensions[0].permutations_explored / 2, "{:?}", sequence);
+            explore(&mut explorer, false);
+        }
+
+        // Functions of more states, and of sets of rules
+        let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,3,5,7]);
+        explorer.set_verbose(false);
+        explorer.set_states(3);
+        assert!(!explore(&mut explorer, true).matches().is_empty());
+        explorer.set_states(2);
+        explorer.set_rule_set(2, Policy::Majority);
+        explorer.set_threads(3);
+        assert!(!explore(&mut explorer, true).matches().is_empty());
+    }
+
+    #[test]
+    fn test_functions() {
+        let mut functions = Functions::default();
+        assert_eq!(functions.get(&[1, 2]), None);
+        functions.insert(vec![1, 2], true);
+        functions.insert(vec![3, 4], false);
+        assert_eq!(functions.get(&[1, 2]), Some(true));
+        assert_eq!(functions.get(&[3, 4]), Some(false));
+        assert_eq!(functions.get(&[1, 3]), None);
+
+        // A function pushed out of its slot is forgotten
+        let mut functions = Functions { slots: vec![None] };
+        functions.insert(vec![1, 2], true);
+        functions.insert(vec![3, 4], false);
+        assert_eq!(functions.get(&[1, 2]), None);
+        assert_eq!(functions.get(&[3, 4]), Some(false));
+    }
+
+    #[test]
+    fn test_states() {
+        // A third state makes actions to it and conditions testing it
+        let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,3,5,7]);
+        explorer.set_verbose(false);
+        let binary = explorer.search_all();
+        explorer.set_states(3);
+        let ternary = explorer.search_all();
+        assert_eq!(binary.dimensions[0].rules_explored, 64);
+        assert_eq!(ternary.dimensions[0].rules_explored, 192);
+        assert_eq!(ternary.dimensions[0].permutations_explored, 256);
+
+        // Binary rules are among them, testing and giving Set only
+        for found in binary.matches() {
+            let rule = found.rule_set.get_rules()[0].clone().with_states(3);
+            assert!(ternary.matches().iter().any(|other| other.rule_set.get_rules()[0] == rule));
+        }
+
+        // Every cell set in any state counts for the sequence
+        for found in ternary.matches() {
+            assert!(explorer.generates_expected_sequence(&found.rule_set, 1, None));
+        }
+    }
+
+    #[test]
+    fn test_resume() {
+        let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,3,5,7,9]);
+        explorer.set_verbose(false);
+        let expected = explorer.search_all();
+
+        for threads in [1, 3] {
+            explorer.set_threads(threads);
+
+            for start in [1, 21, 40, 96] {
+                let mut cursor = Cursor::new(1);
+                for _ in 1..start {
+                    cursor.advance();
+                }
+
+                let checkpoint = Checkpoint {
+                    expected_num_set_cells: vec![1,3,5,7,9],
+                    min_dimensions: 1,
+                    max_dimensions: 1,
+                    update_mode: UpdateMode::Synchronous,
+                    measure: "population".to_string(),
+                    neighbourhood: Neighbourhood::Moore(1),
+                    topology: Topology::infinite(),
+                    family: Family::Tree,
+                    actions: Actions::new(),
+                    states: 2,
+                    rule_set_len: 1,
+                    policy: Policy::FirstMatch,
+                    exhaustive: true,
+                    explored: vec![],
+                    matches: expected.matches().into_iter().filter(|found| found.index < start).cloned().collect(),
+                    pruned: 0,
+                    cursor
+                };
+
+                // Candidates computing the function of one before the cursor are simulated
+                // again, so fewer are pruned
+                let resumed = explorer.resume(checkpoint).unwrap();
+                let (resumed, uninterrupted) = (&resumed.dimensions[0], &expected.dimensions[0]);
+                assert_eq!(resumed.matches, uninterrupted.matches);
+                assert_eq!((resumed.rules_explored, resumed.permutations_explored, resumed.exhausted), (uninterrupted.rules_explored, uninterrupted.permutations_explored, uninterrupted.exhausted));
+                assert!(resumed.pruned <= uninterrupted.pruned);
+            }
+        }
+
+        let checkpoint = Checkpoint {
+            expected_num_set_cells: vec![1,3,5],
+            min_dimensions: 1,
+            max_dimensions: 1,
+            update_mode: UpdateMode::Synchronous,
+            measure: "population".to_string(),
+            neighbourhood: Neighbourhood::Moore(1),
+            topology: Topology::infinite(),
+            family: Family::Tree,
+            actions: Actions::new(),
+            states: 2,
+            rule_set_len: 1,
+            policy: Policy::FirstMatch,
+            exhaustive: true,
+            explored: vec![],
+            matches: vec![],
+            pruned: 0,
+            cursor: Cursor::new(1)
+        };
+        assert!(explorer.resume(checkpoint).is_err());
+    }
+
+    #[test]
+    fn test_checkpoint_written_while_exploring() {
+        let path = std::env::temp_dir().join(format!("cas3_checkpoint_{}.txt", std::process::id()));
+
+        for threads in [1, 2] {
+            let mut explorer: StateExplorer = StateExplorer::new(1, 1, vec![1,2,3,4,5]);
+            explorer.set_verbose(false);
+            explorer.set_threads(threads);
+            let expected = explorer.search_all();
+
+            // Checkpoint at every candidate, the last one written
+            // is past the explored dimensions
+            // With several threads, the pruned count of two runs may differ
+            explorer.set_checkpoint(&path, Duration::ZERO);
+            let checkpointed = explorer.search_all();
+            assert_eq!(checkpointed.matches(), expected.matches());
+            assert_eq!(checkpointed.dimensions.iter().map(|dimension| (dimension.rules_explored, dimension.permutations_explored, dimension.exhausted)).collect::<Vec<_>>(),
+                expected.dimensions.iter().map(|dimension| (dimension.rules_explored, dimension.permutations_explored, dimension.exhausted)).collect::<Vec<_>>());
+
+            let checkpoint = Checkpoint::load(&path).unwrap();
+            assert!(checkpoint.exhaustive);
+            assert_eq!(checkpoint.cursor.dimension, 2);
+            assert_eq!(checkpoint.explored, checkpointed.dimensions);
+
+            let mut resumed_explorer = StateExplorer::from_checkpoint(&checkpoint);
+            resumed_explorer.set_verbose(false);
+            assert_eq!(resumed_explorer.resume(checkpoint).unwrap(), checkpointed);
+        }
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
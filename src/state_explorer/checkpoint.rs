@@ -0,0 +1,470 @@
+/*
+* Checkpoints of a running exploration
+*
+* A checkpoint holds everything needed to continue an exploration exactly
+* where it stopped: the explorer's parameters, the results of the dimensions
+* already explored, the matches found so far in the current dimension, and the
+* cursor, i.e. the next candidate to explore along with the enumeration state of
+* its rules (operation and split index of every OpNode, permuter stack).
+*
+* Saved as text, one entry per line:
+*
+*   checkpoint 1
+*   sequence 1,3,5
+*   dimensions 1 2
+*   update synchronous
+*   measure population
+*   neighbourhood moore
+*   boundary infinite
+*   family tree
+*   actions set,flip
+*   states 2
+*   rule-set 1 first-match
+*   exhaustive 1
+*   explored <dimension> <rules> <permutations> <exhausted> <pruned>
+*   match <dimension> <index> <rule set state>
+*   pruned <candidates>
+*   cursor <dimension> <index> <rule number> <rule set state>
+*/
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::rule::{Actions, Family};
+use crate::rule_set::{Policy, RuleSet};
+use crate::measure;
+use crate::neighbourhood::Neighbourhood;
+use crate::space::UpdateMode;
+use crate::state_explorer::{Cursor, DimensionResult, RuleMatch};
+use crate::topology::Topology;
+
+const VERSION: usize = 1;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    pub expected_num_set_cells: Vec<usize>,
+    pub min_dimensions: usize,
+    pub max_dimensions: usize,
+    pub update_mode: UpdateMode,
+    // Name of the measure, see measure::from_name
+    pub measure: String,
+    // Neighbourhood of the rules
+    pub neighbourhood: Neighbourhood,
+    // Boundaries of the space
+    pub topology: Topology,
+    // Family of the rules
+    pub family: Family,
+    // Actions enumerated for each condition
+    pub actions: Actions,
+    // Number of states of the cells
+    pub states: usize,
+    // Number of rules of each candidate, and how they combine
+    pub rule_set_len: usize,
+    pub policy: Policy,
+    pub exhaustive: bool,
+    // Dimensions explored before the cursor's
+    pub explored: Vec<DimensionResult>,
+    // Matches in the cursor's dimension, before the cursor
+    pub matches: Vec<RuleMatch>,
+    // Candidates of the cursor's dimension pruned before the cursor
+    pub pruned: usize,
+    pub cursor: Cursor
+}
+
+impl Checkpoint {
+    pub fn to_text(&self) -> String {
+        let mut lines = vec![
+            format!("checkpoint {}", VERSION),
+            format!("sequence {}", self.expected_num_set_cells.iter().map(|el| el.to_string()).collect::<Vec<String>>().join(",")),
+            format!("dimensions {} {}", self.min_dimensions, self.max_dimensions),
+            format!("update {}", self.update_mode.name()),
+            format!("measure {}", self.measure),
+            format!("neighbourhood {}", self.neighbourhood.name()),
+            format!("boundary {}", self.topology.name()),
+            format!("family {}", self.family.name()),
+            format!("actions {}", self.actions.name()),
+            format!("states {}", self.states),
+            format!("rule-set {} {}", self.rule_set_len, self.policy.name()),
+            format!("exhaustive {}", self.exhaustive as usize)
+        ];
+
+        for dimension in self.explored.iter() {
+            lines.push(format!("explored {} {} {} {} {}", dimension.dimension, dimension.rules_explored, dimension.permutations_explored, dimension.exhausted as usize, dimension.pruned));
+            for rule_match in dimension.matches.iter() {
+                lines.push(Checkpoint::match_line(rule_match));
+            }
+        }
+
+        for rule_match in self.matches.iter() {
+            lines.push(Checkpoint::match_line(rule_match));
+        }
+
+        lines.push(format!("pruned {}", self.pruned));
+        lines.push(format!("cursor {} {} {} {}", self.cursor.dimension, self.cursor.index, self.cursor.rule_number, self.cursor.rule_set.get_state()));
+
+        lines.join("\n") + "\n"
+    }
+
+    fn match_line(rule_match: &RuleMatch) -> String {
+        format!("match {} {} {}", rule_match.dimension, rule_match.index, rule_match.rule_set.get_state())
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut version = None;
+        let mut sequence = None;
+        let mut dimensions = None;
+        let mut update_mode = None;
+        let mut measure = None;
+        let mut neighbourhood = None;
+        let mut topology = None;
+        let mut family = None;
+        let mut actions = None;
+        let mut states = None;
+        let mut rule_set_len = None;
+        let mut policy = None;
+        let mut exhaustive = None;
+        let mut explored: Vec<DimensionResult> = vec![];
+        let mut matches: Vec<RuleMatch> = vec![];
+        let mut pruned = None;
+        let mut cursor = None;
+
+        for line in text.lines().filter(|line| !line.trim().is_empty()) {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            let mut fields = value.split_whitespace();
+
+            match key {
+                "checkpoint" => version = Some(parse_field(&mut fields, line)?),
+                "sequence" => {
+                    let mut v = vec![];
+                    for el in value.trim().split(',') {
+                        v.push(el.parse::<usize>().map_err(|_| format!("Invalid sequence in {}", line))?);
+                    }
+                    sequence = Some(v);
+                },
+                "dimensions" => dimensions = Some((parse_field(&mut fields, line)?, parse_field(&mut fields, line)?)),
+                "update" => update_mode = Some(UpdateMode::from_name(value.trim()).ok_or(format!("Unknown update mode in {}", line))?),
+                "measure" => measure = Some(measure::from_name(value.trim())?.name()),
+                // Comes before the rules, which are read in this neighbourhood
+                "neighbourhood" => neighbourhood = Some(Neighbourhood::from_name(value.trim()).ok_or(format!("Unknown neighbourhood in {}", line))?),
+                "boundary" => topology = Some(Topology::from_name(value.trim()).ok_or(format!("Unknown boundary in {}", line))?),
+                "family" => family = Some(Family::from_name(value.trim()).ok_or(format!("Unknown rule family in {}", line))?),
+                "actions" => actions = Some(Actions::from_name(value.trim()).ok_or(format!("Unknown actions in {}", line))?),
+                "states" => states = Some(parse_field(&mut fields, line)?),
+                "rule-set" => {
+                    rule_set_len = Some(parse_field(&mut fields, line)?);
+                    policy = Some(fields.next().and_then(Policy::from_name).ok_or(format!("Unknown rule set policy in {}", line))?);
+                },
+                "exhaustive" => exhaustive = Some(parse_field(&mut fields, line)? == 1),
+                "explored" => {
+                    let mut dimension = DimensionResult::new(parse_field(&mut fields, line)?);
+                    dimension.rules_explored = parse_field(&mut fields, line)?;
+                    dimension.permutations_explored = parse_field(&mut fields, line)?;
+                    dimension.exhausted = parse_field(&mut fields, line)? == 1;
+                    dimension.pruned = parse_field(&mut fields, line)?;
+                    explored.push(dimension);
+                },
+                "match" => {
+                    let dim_len = parse_field(&mut fields, line)?;
+                    let index = parse_field(&mut fields, line)?;
+                    let rule_set = RuleSet::from_state(dim_len, neighbourhood.ok_or("Checkpoint has no neighbourhood before its rules")?, &remaining(value, 2))?;
+                    let rule_match = RuleMatch { dimension: dim_len, index, rule_set };
+
+                    match explored.iter_mut().find(|dimension| dimension.dimension == dim_len) {
+                        Some(dimension) => dimension.matches.push(rule_match),
+                        None => matches.push(rule_match)
+                    }
+                },
+                "pruned" => pruned = Some(parse_field(&mut fields, line)?),
+                "cursor" => {
+                    let dim_len = parse_field(&mut fields, line)?;
+                    let index = parse_field(&mut fields, line)?;
+                    let rule_number = parse_field(&mut fields, line)?;
+                    let rule_set = RuleSet::from_state(dim_len, neighbourhood.ok_or("Checkpoint has no neighbourhood before its rules")?, &remaining(value, 3))?;
+                    cursor = Some(Cursor { dimension: dim_len, index, rule_number, rule_set });
+                },
+                _ => return Err(format!("Unknown checkpoint entry {}", line))
+            }
+        }
+
+        if version != Some(VERSION) {
+            return Err(format!("Unsupported checkpoint version {:?}", version));
+        }
+
+        let (min_dimensions, max_dimensions) = dimensions.ok_or("Checkpoint has no dimensions")?;
+        let family = family.ok_or("Checkpoint has no rule family")?;
+        let states = states.ok_or("Checkpoint has no number of states")?;
+        family.supports_states(states)?;
+        let cursor: Cursor = cursor.ok_or("Checkpoint has no cursor")?;
+        if matches.iter().any(|rule_match| rule_match.dimension != cursor.dimension) {
+            return Err("Checkpoint has matches outside the explored dimensions".to_string());
+        }
+
+        Ok(Checkpoint {
+            expected_num_set_cells: sequence.ok_or("Checkpoint has no sequence")?,
+            min_dimensions,
+            max_dimensions,
+            update_mode: update_mode.ok_or("Checkpoint has no update mode")?,
+            measure: measure.ok_or("Checkpoint has no measure")?,
+            neighbourhood: neighbourhood.ok_or("Checkpoint has no neighbourhood")?,
+            topology: topology.ok_or("Checkpoint has no boundary")?,
+            family,
+            actions: actions.ok_or("Checkpoint has no actions")?,
+            states,
+            rule_set_len: rule_set_len.ok_or("Checkpoint has no rule set entry")?,
+            policy: policy.ok_or("Checkpoint has no rule set entry")?,
+            exhaustive: exhaustive.ok_or("Checkpoint has no exhaustive entry")?,
+            explored,
+            matches,
+            pruned: pruned.ok_or("Checkpoint has no pruned entry")?,
+            cursor
+        })
+    }
+
+    // Writes to a temporary file first, so an interruption never leaves a partial checkpoint
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+
+        fs::write(&tmp_path, self.to_text())?;
+        fs::rename(&tmp_path, path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+        Checkpoint::from_text(&text)
+    }
+}
+
+fn parse_field<'a>(fields: &mut impl Iterator<Item = &'a str>, line: &str) -> Result<usize, String> {
+    fields.next()
+        .and_then(|field| field.parse::<usize>().ok())
+        .ok_or(format!("Invalid checkpoint entry {}", line))
+}
+
+// value without its first n fields
+fn remaining(value: &str, n: usize) -> String {
+    value.split_whitespace().skip(n).collect::<Vec<&str>>().join(" ")
+}
+
+// Saves checkpoints of an exploration every interval
+pub struct CheckpointWriter {
+    path: PathBuf,
+    interval: Duration,
+    last_write: Instant,
+    // Everything but the position within the current dimension
+    checkpoint: Checkpoint
+}
+
+impl CheckpointWriter {
+    pub fn new(path: &Path, interval: Duration, checkpoint: Checkpoint) -> Self {
+        CheckpointWriter {
+            path: path.to_path_buf(),
+            interval,
+            last_write: Instant::now(),
+            checkpoint
+        }
+    }
+
+    pub fn is_due(&self) -> bool {
+        self.last_write.elapsed() >= self.interval
+    }
+
+    pub fn finish_dimension(&mut self, result: &DimensionResult) {
+        self.checkpoint.explored.push(result.clone());
+    }
+
+    // Failing to checkpoint shouldn't end a long exploration, so errors are only reported
+    pub fn write(&mut self, matches: &[RuleMatch], pruned: usize, cursor: &Cursor) {
+        self.checkpoint.matches = matches.to_vec();
+        self.checkpoint.pruned = pruned;
+        self.checkpoint.cursor = cursor.clone();
+
+        if let Err(err) = self.checkpoint.save(&self.path) {
+            eprintln!("Could not write checkpoint {}: {}", self.path.display(), err);
+        }
+        self.last_write = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rule::Rule;
+
+    #[test]
+    fn test_checkpoint_round_trip() {
+        let mut rule = Rule::new(1);
+        for _ in 0..20 {
+            rule.generate_next_candidate();
+        }
+
+        let mut explored = DimensionResult::new(1);
+        explored.rules_explored = 64;
+        explored.permutations_explored = 96;
+        explored.exhausted = true;
+        explored.pruned = 12;
+        explored.matches.push(RuleMatch { dimension: 1, index: 21, rule_set: RuleSet::from_rule(&rule) });
+
+        let mut cursor = Cursor::new(2);
+        for _ in 0..100 {
+            cursor.advance();
+        }
+
+        let checkpoint = Checkpoint {
+            expected_num_set_cells: vec![1, 3, 5],
+            min_dimensions: 1,
+            max_dimensions: 2,
+            update_mode: UpdateMode::Synchronous,
+            measure: "largest-component:von-neumann".to_string(),
+            neighbourhood: Neighbourhood::Moore(1),
+            topology: Topology::infinite(),
+            family: Family::Tree,
+            actions: Actions::new(),
+            states: 2,
+            rule_set_len: 1,
+            policy: Policy::FirstMatch,
+            exhaustive: true,
+            explored: vec![explored],
+            matches: vec![RuleMatch { dimension: 2, index: 7, rule_set: RuleSet::from_rule(&Rule::new(2)) }],
+            pruned: 30,
+            cursor
+        };
+
+        let text = checkpoint.to_text();
+        assert_eq!(Checkpoint::from_text(&text).unwrap(), checkpoint);
+
+        assert!(Checkpoint::from_text(&text.replace("checkpoint 1", "checkpoint 2")).is_err());
+        assert!(Checkpoint::from_text(&text.replace("cursor", "kursor")).is_err());
+        assert!(Checkpoint::from_text(&text.replace("match 2", "match 3")).is_err());
+        assert!(Checkpoint::from_text(&text.replace("update synchronous", "update sideways")).is_err());
+        assert!(Checkpoint::from_text(&text.replace("measure largest-component", "measure smallest-component")).is_err());
+
+
+        assert!(text.contains("explored 1 64 96 1 12\n"));
+        assert!(Checkpoint::from_text(&text.replace("explored 1 64 96 1 12", "explored 1 64 96 1")).is_err());
+
+        // Rules of the von Neumann neighbourhood can't be read as Moore rules
+        let mut cursor = Cursor::with_neighbourhood(2, Neighbourhood::VonNeumann(1));
+        for _ in 0..100 {
+            cursor.advance();
+        }
+        let von_neumann = Checkpoint {
+            neighbourhood: Neighbourhood::VonNeumann(1),
+            explored: vec![],
+            matches: vec![],
+            cursor,
+            ..checkpoint
+        };
+        let text = von_neumann.to_text();
+        assert_eq!(Checkpoint::from_text(&text).unwrap(), von_neumann);
+        assert!(Checkpoint::from_text(&text.replace("neighbourhood von-neumann", "neighbourhood moore")).is_err());
+
+        let mut cursor = Cursor::with_family(2, Neighbourhood::VonNeumann(1), Family::OuterTotalistic);
+        for _ in 0..100 {
+            cursor.advance();
+        }
+        let outer_totalistic = Checkpoint {
+            family: Family::OuterTotalistic,
+            cursor,
+            ..von_neumann
+        };
+        let text = outer_totalistic.to_text();
+        assert!(text.contains("family outer-totalistic\n"));
+        assert!(text.contains("outer-totalistic 100\n"));
+        assert_eq!(Checkpoint::from_text(&text).unwrap(), outer_totalistic);
+        assert!(Checkpoint::from_text(&text.replace("family outer-totalistic", "family hexagonal")).is_err());
+
+        // Rules of the cursor carry their actions
+        let mut cursor = Cursor::with_rule(1, Rule::with_actions(1, Neighbourhood::VonNeumann(1), Actions { unset: true, else_action: true }));
+        for _ in 0..30 {
+            cursor.advance();
+        }
+        let with_actions = Checkpoint {
+            family: Family::Tree,
+            actions: cursor.rule_set.get_rules()[0].get_actions(),
+            cursor,
+            ..outer_totalistic
+        };
+        let text = with_actions.to_text();
+        assert!(text.contains("actions set,flip,unset,else\n"));
+        assert_eq!(Checkpoint::from_text(&text).unwrap(), with_actions);
+        assert!(Checkpoint::from_text(&text.replace("actions set,flip,unset,else", "actions set,flip,kill")).is_err());
+
+        // Sets of rules
+        let mut cursor = Cursor::with_rule_set(1, RuleSet::first(3, Policy::Majority, Rule::new(1)));
+        for _ in 0..1000 {
+            cursor.advance();
+        }
+        let rule_set = Checkpoint {
+            actions: Actions::new(),
+            neighbourhood: Neighbourhood::Moore(1),
+            rule_set_len: 3,
+            policy: Policy::Majority,
+            cursor,
+            ..with_actions.clone()
+        };
+        let text = rule_set.to_text();
+        assert!(text.contains("rule-set 3 majority\n"));
+        assert_eq!(Checkpoint::from_text(&text).unwrap(), rule_set);
+        assert!(Checkpoint::from_text(&text.replace("rule-set 3 majority", "rule-set 3 minority")).is_err());
+
+        // Rules of the cursor carry their states, which the family has to support
+        let mut cursor = Cursor::with_rule(1, Rule::new(1).with_states(3));
+        for _ in 0..100 {
+            cursor.advance();
+        }
+        let states = Checkpoint {
+            states: 3,
+            rule_set_len: 1,
+            policy: Policy::FirstMatch,
+            cursor,
+            ..rule_set
+        };
+        let text = states.to_text();
+        assert!(text.contains("states 3\n"));
+        assert_eq!(Checkpoint::from_text(&text).unwrap(), states);
+        assert!(Checkpoint::from_text(&text.replace("family tree", "family totalistic")).is_err());
+
+        // Boundaries of each axis
+        let bounded = Checkpoint {
+            topology: Topology::from_name("torus:10,dead:4").unwrap(),
+            ..states
+        };
+        let text = bounded.to_text();
+        assert!(text.contains("boundary torus:10,dead:4\n"));
+        assert_eq!(Checkpoint::from_text(&text).unwrap(), bounded);
+        assert!(Checkpoint::from_text(&text.replace("torus:10", "sphere:10")).is_err());
+    }
+
+    #[test]
+    fn test_checkpoint_missing_entries() {
+        let text = Checkpoint {
+            expected_num_set_cells: vec![1, 3, 5],
+            min_dimensions: 1,
+            max_dimensions: 2,
+            update_mode: UpdateMode::Asynchronous,
+            measure: "population".to_string(),
+            neighbourhood: Neighbourhood::Moore(1),
+            topology: Topology::infinite(),
+            family: Family::Tree,
+            actions: Actions::new(),
+            states: 2,
+            rule_set_len: 1,
+            policy: Policy::FirstMatch,
+            exhaustive: false,
+            explored: vec![],
+            matches: vec![],
+            pruned: 0,
+            cursor: Cursor::new(1)
+        }.to_text();
+        assert!(Checkpoint::from_text(&text).is_ok());
+
+        // A truncated or edited checkpoint would resume another search
+        for key in ["checkpoint", "sequence", "dimensions", "update", "measure", "neighbourhood", "boundary", "family", "actions", "states", "rule-set", "exhaustive", "pruned", "cursor"] {
+            let truncated: Vec<&str> = text.lines().filter(|line| !line.starts_with(&format!("{} ", key))).collect();
+            assert!(Checkpoint::from_text(&truncated.join("\n")).is_err(), "{}", key);
+        }
+    }
+}
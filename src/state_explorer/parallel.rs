@@ -0,0 +1,339 @@
+/*
+* Parallel exploration of a dimension
+*
+* The enumeration of rules and evaluation permutations is inherently sequential,
+* but simulating a candidate is independent of every other candidate.
+* So the calling thread walks the enumeration and cuts it into work units of
+* consecutive candidates, and a pool of workers simulates them.
+*
+* Units are numbered in enumeration order and results are merged by unit number,
+* so the result is the same as exploring on a single thread, whatever the
+* number of workers or the order in which they finish.
+*
+* Workers also fingerprint each candidate, and don't simulate those computing a
+* function whose result is known, from a unit already merged or an earlier
+* candidate of their unit. Those are counted as pruned, so the count depends on
+* the order units finish in, but the result of every candidate doesn't. The
+* results of the functions are remembered when merging, in enumeration order.
+*/
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Mutex, RwLock};
+use std::thread;
+
+use crate::rule_set::RuleSet;
+use crate::state_explorer::checkpoint::CheckpointWriter;
+use crate::state_explorer::{Cursor, DimensionResult, Functions, RuleMatch, StateExplorer};
+
+// Number of consecutive candidates in a work unit
+const WORK_UNIT_LEN: usize = 64;
+
+struct WorkUnit {
+    id: usize,
+    // First candidate of the unit
+    cursor: Cursor,
+    // Number of candidates
+    len: usize
+}
+
+struct WorkUnitResult {
+    id: usize,
+    // Position, rule counter and rule set of the last candidate explored
+    last_index: usize,
+    last_rule_number: usize,
+    last_rule_set: RuleSet,
+    // Candidates with a fingerprint or matching, in order
+    outcomes: Vec<Outcome>
+}
+
+// Candidate of a unit the merge needs
+struct Outcome {
+    // See StateExplorer::fingerprint
+    fingerprint: Option<Vec<u8>>,
+    // Whether the worker took the known result of its function instead of simulating it
+    cached: bool,
+    // The candidate and its rule counter, when it generates the expected sequence
+    matched: Option<(RuleMatch, usize)>
+}
+
+// Tracks which units are done, so a checkpoint never skips a unit
+// still being explored, and merges them in order
+struct Frontier<'a> {
+    // Units dispatched and not merged yet, by their first candidate
+    pending: BTreeMap<usize, Cursor>,
+    // Units finished ahead of the frontier
+    finished: BTreeMap<usize, WorkUnitResult>,
+    // Number of units before the frontier
+    merged: usize,
+    // Result of the units before the frontier
+    result: DimensionResult,
+    // Results of the functions of the units before the frontier
+    functions: &'a RwLock<Functions>,
+    exhaustive: bool,
+    // Prints the last candidate of each unit merged
+    verbose: bool,
+    // Whether a match ended the exploration, when not exhaustive
+    stopped: bool
+}
+
+impl<'a> Frontier<'a> {
+    // result holds what was found before the first unit
+    fn new(result: DimensionResult, functions: &'a RwLock<Functions>, exhaustive: bool, verbose: bool) -> Self {
+        Frontier {
+            pending: BTreeMap::new(),
+            finished: BTreeMap::new(),
+            merged: 0,
+            result,
+            functions,
+            exhaustive,
+            verbose,
+            stopped: false
+        }
+    }
+
+    fn dispatch(&mut self, unit: &WorkUnit) {
+        self.pending.insert(unit.id, unit.cursor.clone());
+    }
+
+    fn finish(&mut self, unit_result: WorkUnitResult) {
+        self.finished.insert(unit_result.id, unit_result);
+
+        // Merge every unit right after the frontier, which may have finished earlier
+        while let Some(unit_result) = self.finished.remove(&self.merged) {
+            self.pending.remove(&unit_result.id);
+            self.merged += 1;
+            self.merge(unit_result);
+        }
+    }
+
+    fn merge(&mut self, unit_result: WorkUnitResult) {
+        if self.stopped {
+            return;
+        }
+        self.result.rules_explored = unit_result.last_rule_number;
+        self.result.permutations_explored = unit_result.last_index;
+        if self.verbose {
+            StateExplorer::print_progress(&unit_result.last_rule_set, &self.result);
+        }
+
+        let mut functions = self.functions.write().unwrap();
+        for outcome in unit_result.outcomes {
+            if outcome.cached {
+                self.result.pruned += 1;
+            }
+            if let Some(fingerprint) = outcome.fingerprint.filter(|fingerprint| functions.get(fingerprint).is_none()) {
+                functions.insert(fingerprint, outcome.matched.is_some());
+            }
+            let Some((rule_match, rule_number)) = outcome.matched else {
+                continue;
+            };
+            println!("All elements matched for rule");
+            rule_match.rule_set.print();
+
+            if !self.exhaustive {
+                self.result.rules_explored = rule_number;
+                self.result.permutations_explored = rule_match.index;
+                self.result.matches.push(rule_match);
+                self.stopped = true;
+                return;
+            }
+            self.result.matches.push(rule_match);
+        }
+    }
+
+    // First candidate not explored yet, or None if every unit dispatched is done
+    fn cursor(&self) -> Option<&Cursor> {
+        self.pending.values().next()
+    }
+
+    // Units left unmerged follow a unit skipped after the first match, so can't change the result
+    fn into_result(self) -> DimensionResult {
+        let mut result = self.result;
+        result.exhausted = !self.stopped;
+        result
+    }
+}
+
+// Explores from the cursor on, before holding what was found before the cursor
+pub fn explore_dimension(explorer: &StateExplorer, cursor: Cursor, before: DimensionResult, exhaustive: bool, threads: usize, writer: &mut Option<CheckpointWriter>) -> DimensionResult {
+    let (unit_sender, unit_receiver) = mpsc::sync_channel::<WorkUnit>(threads * 2);
+    let unit_receiver = Mutex::new(unit_receiver);
+    let (result_sender, result_receiver) = mpsc::channel::<WorkUnitResult>();
+
+    // Lowest unit with a match. Unless exploring exhaustively,
+    // units after it can't change the result and are skipped
+    let first_matched_unit = AtomicUsize::new(usize::MAX);
+
+    let functions = RwLock::new(Functions::default());
+    let mut frontier = Frontier::new(before, &functions, exhaustive, explorer.verbose);
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let result_sender = result_sender.clone();
+            let unit_receiver = &unit_receiver;
+            let first_matched_unit = &first_matched_unit;
+            let functions = &functions;
+
+            scope.spawn(move || loop {
+                // Lock is released as soon as a unit is received
+                let unit = match unit_receiver.lock().unwrap().recv() {
+                    Ok(unit) => unit,
+                    Err(_) => break
+                };
+
+                if !exhaustive && unit.id > first_matched_unit.load(Ordering::Relaxed) {
+                    continue;
+                }
+
+                let result = explore_unit(explorer, unit, functions);
+                if !exhaustive && result.outcomes.iter().any(|outcome| outcome.matched.is_some()) {
+                    first_matched_unit.fetch_min(result.id, Ordering::Relaxed);
+                }
+                result_sender.send(result).unwrap();
+            });
+        }
+        drop(result_sender);
+
+        let mut cursor = cursor;
+        let mut id = 0;
+        loop {
+            if !exhaustive && id > first_matched_unit.load(Ordering::Relaxed) {
+                break;
+            }
+
+            // Move on to the first candidate of the next unit
+            let first = cursor.clone();
+            let mut len = 0;
+            let mut last_unit = false;
+            while len < WORK_UNIT_LEN {
+                len += 1;
+                if !cursor.has_next() {
+                    last_unit = true;
+                    break;
+                }
+                cursor.advance();
+            }
+
+            let unit = WorkUnit {
+                id,
+                cursor: first,
+                len
+            };
+            frontier.dispatch(&unit);
+
+            unit_sender.send(unit).unwrap();
+            while let Ok(unit_result) = result_receiver.try_recv() {
+                frontier.finish(unit_result);
+            }
+
+            if let Some(writer) = writer.as_mut().filter(|writer| writer.is_due()) {
+                let frontier_cursor = frontier.cursor().unwrap_or(&cursor);
+                writer.write(&frontier.result.matches, frontier.result.pruned, frontier_cursor);
+            }
+
+            if last_unit {
+                break;
+            }
+            id += 1;
+        }
+        drop(unit_sender);
+
+        for unit_result in result_receiver.iter() {
+            frontier.finish(unit_result);
+        }
+    });
+
+    frontier.into_result()
+}
+
+// functions holds the results of the units merged so far
+fn explore_unit(explorer: &StateExplorer, unit: WorkUnit, functions: &RwLock<Functions>) -> WorkUnitResult {
+    let mut cursor = unit.cursor;
+    let mut outcomes = vec![];
+    // Results of the functions of the unit
+    let mut unit_functions: HashMap<Vec<u8>, bool> = HashMap::new();
+
+    for i in 0..unit.len {
+        let fingerprint = explorer.fingerprint(&cursor.rule_set);
+        let known = fingerprint.as_ref().and_then(|fingerprint| {
+            unit_functions.get(fingerprint).copied().or_else(|| functions.read().unwrap().get(fingerprint))
+        });
+        let matched = known.unwrap_or_else(|| explorer.generates_expected_sequence(&cursor.rule_set, cursor.dimension, None));
+        if let Some(fingerprint) = &fingerprint {
+            unit_functions.entry(fingerprint.clone()).or_insert(matched);
+        }
+
+        if fingerprint.is_some() || matched {
+            outcomes.push(Outcome {
+                fingerprint,
+                cached: known.is_some(),
+                matched: matched.then(|| (RuleMatch {
+                    dimension: cursor.dimension,
+                    index: cursor.index,
+                    rule_set: cursor.rule_set.clone()
+                }, cursor.rule_number))
+            });
+        }
+
+        if i + 1 == unit.len {
+            break;
+        }
+        cursor.advance();
+    }
+
+    WorkUnitResult {
+        id: unit.id,
+        last_index: cursor.index,
+        last_rule_number: cursor.rule_number,
+        last_rule_set: cursor.rule_set,
+        outcomes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn quiet_explorer(sequence: Vec<usize>) -> StateExplorer {
+        let mut explorer = StateExplorer::new(1, 1, sequence);
+        explorer.set_verbose(false);
+        explorer
+    }
+
+    // Checks a parallel result against the sequential one. Workers only know the functions of
+    // the units merged before they took theirs, so they may prune fewer candidates
+    fn assert_same_result(parallel: &DimensionResult, sequential: &DimensionResult) {
+        assert_eq!(parallel.matches, sequential.matches);
+        assert_eq!(parallel.rules_explored, sequential.rules_explored);
+        assert_eq!(parallel.permutations_explored, sequential.permutations_explored);
+        assert_eq!(parallel.exhausted, sequential.exhausted);
+        assert!(parallel.pruned <= sequential.pruned);
+    }
+
+    #[test]
+    fn test_parallel_matches_sequential() {
+        for sequence in [vec![1,3,5,7,9,11,13,15], vec![1,2,3,4,5,6,7,8,9], vec![1,2,3,5,7,11]] {
+            let explorer = quiet_explorer(sequence);
+
+            for exhaustive in [true, false] {
+                let expected = explorer.explore_dimension(Cursor::new(1), DimensionResult::new(1), exhaustive, &mut None);
+                for threads in [2, 3, 8] {
+                    assert_same_result(&explore_dimension(&explorer, Cursor::new(1), DimensionResult::new(1), exhaustive, threads, &mut None), &expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_2d() {
+        let explorer = quiet_explorer(vec![1,2,3,4,5,6,7,8,9]);
+
+        let expected = explorer.explore_dimension(Cursor::new(2), DimensionResult::new(2), false, &mut None);
+        let result = explore_dimension(&explorer, Cursor::new(2), DimensionResult::new(2), false, 4, &mut None);
+        assert_same_result(&result, &expected);
+        assert!(!result.exhausted);
+        assert_eq!(result.matches.len(), 1);
+    }
+}
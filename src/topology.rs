@@ -0,0 +1,150 @@
+/*
+* Topologies
+*
+* The boundary of the space along each axis. Spaces are infinite by default,
+* growing around every set cell. A bounded axis of length n holds the cells
+* -(n/2) to (n - 1)/2 around the starting cell, and its boundary decides what
+* the cells beyond it are:
+* - dead: cells beyond the edge are unset and never added
+* - torus: the axis wraps around, the cell past the last one being the first
+* - reflect: the axis is mirrored at the edge, the cell past the last one being
+*   the last one, then the one before it ..
+*
+* Names are "infinite", "dead:n", "torus:n" and "reflect:n". A topology is the
+* boundaries of the axes separated by ",", the last one applying to the
+* remaining axes, so "torus:20" is a torus along every axis.
+*/
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Boundary {
+    Infinite,
+    Dead(usize),
+    Torus(usize),
+    Reflect(usize)
+}
+
+impl Boundary {
+    pub fn name(&self) -> String {
+        match self {
+            Boundary::Infinite => "infinite".to_string(),
+            Boundary::Dead(len) => format!("dead:{}", len),
+            Boundary::Torus(len) => format!("torus:{}", len),
+            Boundary::Reflect(len) => format!("reflect:{}", len)
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Boundary> {
+        let (boundary, len) = match name.split_once(':') {
+            Some((boundary, len)) => (boundary, Some(len.parse::<usize>().ok().filter(|len| *len > 0)?)),
+            None => (name, None)
+        };
+
+        match (boundary, len) {
+            ("infinite", None) => Some(Boundary::Infinite),
+            ("dead", Some(len)) => Some(Boundary::Dead(len)),
+            ("torus", Some(len)) => Some(Boundary::Torus(len)),
+            ("reflect", Some(len)) => Some(Boundary::Reflect(len)),
+            _ => None
+        }
+    }
+
+    // Coordinate of the cell of the axis at the coordinate, None beyond a dead edge
+    pub fn resolve(&self, coordinate: i32) -> Option<i32> {
+        let len = match self {
+            Boundary::Infinite => return Some(coordinate),
+            Boundary::Dead(len) | Boundary::Torus(len) | Boundary::Reflect(len) => *len as i32
+        };
+        let start = -(len / 2);
+        let position = coordinate - start;
+
+        match self {
+            Boundary::Dead(_) => (0..len).contains(&position).then_some(coordinate),
+            Boundary::Torus(_) => Some(start + position.rem_euclid(len)),
+            // Mirrored every len cells, so with a period of 2 len
+            _ => {
+                let position = position.rem_euclid(2 * len);
+                Some(start + if position < len { position } else { 2 * len - 1 - position })
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Topology {
+    boundaries: Vec<Boundary>
+}
+
+impl Topology {
+    pub fn infinite() -> Self {
+        Topology::new(vec![Boundary::Infinite])
+    }
+
+    // The last boundary applies to the axes past the given ones
+    pub fn new(boundaries: Vec<Boundary>) -> Self {
+        if boundaries.is_empty() {
+            return Topology::infinite();
+        }
+        Topology { boundaries }
+    }
+
+    pub fn boundary(&self, axis: usize) -> Boundary {
+        self.boundaries[axis.min(self.boundaries.len() - 1)]
+    }
+
+    pub fn is_infinite(&self) -> bool {
+        self.boundaries.iter().all(|boundary| *boundary == Boundary::Infinite)
+    }
+
+    // Coordinates of the cell of the space at the coordinates, None beyond a dead edge
+    pub fn resolve(&self, coordinates: &[i32]) -> Option<Vec<i32>> {
+        coordinates.iter().enumerate()
+            .map(|(axis, coordinate)| self.boundary(axis).resolve(*coordinate))
+            .collect()
+    }
+
+    pub fn name(&self) -> String {
+        self.boundaries.iter().map(|boundary| boundary.name()).collect::<Vec<String>>().join(",")
+    }
+
+    pub fn from_name(name: &str) -> Option<Topology> {
+        let boundaries = name.split(',').map(|boundary| Boundary::from_name(boundary.trim())).collect::<Option<Vec<Boundary>>>()?;
+        Some(Topology::new(boundaries))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_boundaries() {
+        // Cells -2 to 2
+        let resolve = |boundary: Boundary| (-5..6).map(|el| boundary.resolve(el)).collect::<Vec<Option<i32>>>();
+        assert_eq!(resolve(Boundary::Dead(5)), [None, None, None, Some(-2), Some(-1), Some(0), Some(1), Some(2), None, None, None]);
+        assert_eq!(resolve(Boundary::Torus(5)).into_iter().flatten().collect::<Vec<i32>>(), [0, 1, 2, -2, -1, 0, 1, 2, -2, -1, 0]);
+        assert_eq!(resolve(Boundary::Reflect(5)).into_iter().flatten().collect::<Vec<i32>>(), [0, -1, -2, -2, -1, 0, 1, 2, 2, 1, 0]);
+        assert_eq!(Boundary::Infinite.resolve(-100), Some(-100));
+
+        // Cells -2 to 1
+        assert_eq!(Boundary::Torus(4).resolve(2), Some(-2));
+        assert_eq!(Boundary::Reflect(4).resolve(-3), Some(-2));
+        assert_eq!(Boundary::Dead(1).resolve(0), Some(0));
+        assert_eq!(Boundary::Torus(1).resolve(-1), Some(0));
+    }
+
+    #[test]
+    fn test_topology() {
+        let topology = Topology::from_name("torus:4,dead:3").unwrap();
+        assert_eq!(topology.boundary(0), Boundary::Torus(4));
+        assert_eq!(topology.boundary(2), Boundary::Dead(3));
+        assert_eq!(topology.resolve(&[2, 1, -1]), Some(vec![-2, 1, -1]));
+        assert_eq!(topology.resolve(&[2, 1, 2]), None);
+        assert_eq!(Topology::from_name(&topology.name()), Some(topology));
+
+        assert!(Topology::infinite().is_infinite());
+        assert_eq!(Topology::infinite().name(), "infinite");
+        for name in ["torus", "torus:0", "dead:x", "infinite:3", "sphere:4", "torus:4,"] {
+            assert_eq!(Topology::from_name(name), None);
+        }
+    }
+}